@@ -1,33 +1,103 @@
-use crate::client::Peer;
+use crate::{
+    ben::Query,
+    client::{Peer, Source},
+    magnet::Magnet,
+};
 use clap::{Parser, Subcommand};
+use reqwest::Url;
 use std::path::PathBuf;
 
 #[derive(Subcommand)]
 pub enum Command {
     Decode {
-        input: String,
+        #[arg(required_unless_present = "file")]
+        input: Option<String>,
+        #[arg(short, long, conflicts_with = "input")]
+        file: Option<PathBuf>,
+        /// Reject input that is not canonical bencode.
+        #[arg(long)]
+        strict: bool,
+        /// Print the byte range of every node after the value.
+        #[arg(long)]
+        spans: bool,
+        /// Print only the nodes matching a path such as `info.files[*].path`.
+        #[arg(short, long)]
+        query: Option<Query>,
+        /// Print an indented, annotated tree instead of JSON.
+        #[arg(short, long)]
+        pretty: bool,
+    },
+    /// Encode JSON as bencode, using the mapping `decode` prints.
+    Encode {
+        /// JSON to encode, read from stdin when missing or `-`.
+        input: Option<String>,
+        #[arg(short, long)]
+        output: Option<PathBuf>,
     },
+    /// Show how two bencoded files differ, node by node.
+    Diff { left: PathBuf, right: PathBuf },
     Info {
         path: PathBuf,
+        /// Print the whole metainfo file as an annotated tree.
+        #[arg(long)]
+        raw: bool,
     },
-    Peers {
+    /// Check a metainfo file for problems, exiting with an error if any is found.
+    Validate { path: PathBuf },
+    /// Hash a file or directory into a new metainfo file.
+    Create {
+        path: PathBuf,
+        /// Where to write the torrent, `<name>.torrent` by default.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Tracker URL, repeated for several trackers.
+        #[arg(short, long = "announce", required = true)]
+        announce: Vec<Url>,
+        #[arg(short, long)]
+        comment: Option<String>,
+        /// Restrict peers to the ones given by trackers.
+        #[arg(long)]
+        private: bool,
+        /// Web seed URL, repeated for several seeds.
+        #[arg(short, long = "web-seed")]
+        web_seed: Vec<String>,
+        /// Piece length in bytes, chosen from the content size by default.
+        #[arg(short, long)]
+        piece_length: Option<u64>,
+    },
+    /// Recheck downloaded content against the piece hashes of a torrent.
+    Verify {
+        torrent: PathBuf,
+        /// The downloaded file, or directory for multi-file torrents.
         path: PathBuf,
     },
+    #[command(name = "magnet_parse")]
+    MagnetParse { link: Magnet },
+    /// Fetch the metadata of a magnet link from peers and print it like `info`.
+    #[command(name = "magnet_info")]
+    MagnetInfo { link: Magnet },
+    Peers {
+        /// Torrent file or magnet link.
+        source: Source,
+    },
     Handshake {
-        path: PathBuf,
+        /// Torrent file or magnet link.
+        source: Source,
         peer: Peer,
     },
     #[command(name = "download_piece")]
     DownloadPiece {
         #[arg(short, long)]
         output: PathBuf,
-        torrent: PathBuf,
+        /// Torrent file or magnet link.
+        torrent: Source,
         piece: u32,
     },
     Download {
         #[arg(short, long)]
         output: PathBuf,
-        torrent: PathBuf,
+        /// Torrent file or magnet link.
+        torrent: Source,
     },
 }
 
@@ -1,17 +1,57 @@
+mod borrowed;
+mod de;
 mod decode;
-use std::{collections::HashMap, fmt::Display};
+mod diff;
+mod encode;
+mod error;
+mod incremental;
+mod json;
+mod path;
+mod pretty;
+mod query;
+mod ser;
+pub use borrowed::{BenRef, ListRef, MapRef};
+pub use de::{from_bytes, from_decoded, from_value};
+pub use decode::{Mode, Options};
+pub use incremental::Incremental;
+pub use path::Path;
+pub use query::Query;
+pub use ser::{to_bytes, to_value};
+use std::{collections::BTreeMap, fmt::Display};
 
 pub type List = Vec<Ben>;
-pub type Map = HashMap<String, Ben>;
+/// Dictionary keyed by raw bytes, which keeps keys in canonical bencode order.
+pub type Map = BTreeMap<Vec<u8>, Ben>;
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Ben {
-    String(String),
+    Bytes(Vec<u8>),
     Number(i64),
     List(List),
     Map(Map),
 }
 
+impl Ben {
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Ben::Bytes(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// UTF-8 view of a byte string, if it is valid UTF-8.
+    pub fn as_str(&self) -> Option<&str> {
+        self.as_bytes().and_then(|b| std::str::from_utf8(b).ok())
+    }
+
+    pub fn get(&self, key: impl AsRef<[u8]>) -> Option<&Ben> {
+        match self {
+            Ben::Map(m) => m.get(key.as_ref()),
+            _ => None,
+        }
+    }
+}
+
 impl PartialEq<i64> for Ben {
     fn eq(&self, other: &i64) -> bool {
         match self {
@@ -24,33 +64,12 @@ impl PartialEq<i64> for Ben {
 impl PartialEq<&str> for Ben {
     fn eq(&self, other: &&str) -> bool {
         match self {
-            Ben::String(s) => s == other,
+            Ben::Bytes(b) => b == other.as_bytes(),
             _ => false,
         }
     }
 }
 
-impl From<&Ben> for serde_json::Value {
-    fn from(value: &Ben) -> Self {
-        use serde_json::Value;
-        match value {
-            Ben::String(s) => Value::String(s.clone()),
-            Ben::Number(i) => Value::from(*i),
-            Ben::List(v) => Value::Array(v.iter().map(Value::from).collect()),
-            Ben::Map(m) => {
-                let map = m.iter().map(|x| (x.0.to_owned(), Self::from(x.1))).fold(
-                    serde_json::Map::new(),
-                    |mut map, x| {
-                        map.insert(x.0, x.1);
-                        map
-                    },
-                );
-                Value::Object(map)
-            }
-        }
-    }
-}
-
 impl Display for Ben {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let value = serde_json::Value::from(self);
@@ -64,10 +83,17 @@ mod test {
 
     #[test]
     fn test_display_string() {
-        let b = Ben::String("hernan.rs".into());
+        let b = Ben::Bytes("hernan.rs".into());
         assert_eq!(b.to_string(), r#""hernan.rs""#);
     }
 
+    #[test]
+    fn test_display_binary() {
+        let b = Ben::Bytes(vec![0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(b.as_str(), None);
+        assert_eq!(b.to_string(), r#"{"$hex":"deadbeef"}"#);
+    }
+
     #[test]
     fn test_display_integer() {
         let b = Ben::Number(42);
@@ -79,16 +105,17 @@ mod test {
 
     #[test]
     fn test_display_list() {
-        let b = Ben::List(vec![Ben::String("hello".into()), Ben::Number(82)]);
+        let b = Ben::List(vec![Ben::Bytes("hello".into()), Ben::Number(82)]);
         assert_eq!(b.to_string(), r#"["hello",82]"#);
     }
 
     #[test]
     fn test_display_dict() {
         let mut m: Map = Map::new();
-        m.insert("foo".into(), Ben::String("bar".into()));
+        m.insert("foo".into(), Ben::Bytes("bar".into()));
         m.insert("hello".into(), Ben::Number(82));
+        m.insert(vec![0xff], Ben::Number(1));
         let b: Ben = Ben::Map(m);
-        assert_eq!(b.to_string(), r#"{"foo":"bar","hello":82}"#);
+        assert_eq!(b.to_string(), r#"{"$hex:ff":1,"foo":"bar","hello":82}"#);
     }
 }
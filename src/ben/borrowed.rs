@@ -0,0 +1,62 @@
+use super::Ben;
+use std::collections::BTreeMap;
+
+pub type ListRef<'a> = Vec<BenRef<'a>>;
+pub type MapRef<'a> = BTreeMap<&'a [u8], BenRef<'a>>;
+
+/// Bencode value borrowing its byte strings from the decoded input.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BenRef<'a> {
+    Bytes(&'a [u8]),
+    Number(i64),
+    List(ListRef<'a>),
+    Map(MapRef<'a>),
+}
+
+impl<'a> BenRef<'a> {
+    pub fn get(&self, key: impl AsRef<[u8]>) -> Option<&BenRef<'a>> {
+        match self {
+            BenRef::Map(m) => m.get(key.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<BenRef<'_>> for Ben {
+    fn from(value: BenRef<'_>) -> Self {
+        match value {
+            BenRef::Bytes(b) => Ben::Bytes(b.to_vec()),
+            BenRef::Number(i) => Ben::Number(i),
+            BenRef::List(l) => Ben::List(l.into_iter().map(Ben::from).collect()),
+            BenRef::Map(m) => {
+                Ben::Map(m.into_iter().map(|(k, v)| (k.to_vec(), v.into())).collect())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ben::Options;
+
+    #[test]
+    fn test_borrowed_slices() {
+        let data = std::fs::read("sample.torrent").unwrap();
+        let b = BenRef::decode_with(&data, Options::default())
+            .unwrap()
+            .value;
+        let Some(BenRef::Bytes(pieces)) = b.get("info").and_then(|i| i.get("pieces")) else {
+            panic!("pieces not found")
+        };
+        let range = data.as_ptr_range();
+        assert!(range.contains(&pieces.as_ptr()));
+    }
+
+    #[test]
+    fn test_into_owned() {
+        let b = BenRef::decode_with(b"d1:ali1e2:xyee", Options::default()).unwrap();
+        let owned: Ben = b.value.into();
+        assert_eq!(owned, "d1:ali1e2:xyee".parse::<Ben>().unwrap());
+    }
+}
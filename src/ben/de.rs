@@ -0,0 +1,450 @@
+use crate::ben::{
+    decode::Decoded,
+    error::{Error, ErrorKind},
+    path::Segment,
+    Ben, List, Map, Options,
+};
+use serde::{
+    de::{self, DeserializeOwned, Unexpected, Visitor},
+    forward_to_deserialize_any, Deserialize,
+};
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Deserializes `T` out of a bencode tree.
+pub fn from_value<'de, T: Deserialize<'de>>(value: &'de Ben) -> Result<T> {
+    T::deserialize(Deserializer(value))
+}
+
+/// Deserializes `T` out of a decoded tree. When decoded with spans, errors
+/// point at the offending node by path and byte offset.
+pub fn from_decoded<T: DeserializeOwned>(decoded: &Decoded) -> Result<T> {
+    from_value(&decoded.value).map_err(|mut e| {
+        e.offset = decoded.spans.get(&e.path).map(|s| s.start);
+        e
+    })
+}
+
+/// Decodes `bytes` and deserializes `T` out of them, errors pointing at the
+/// offending node by path and byte offset.
+pub fn from_bytes<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    let options = Options {
+        spans: true,
+        ..Default::default()
+    };
+    from_decoded(&Ben::decode_with(bytes, options)?)
+}
+
+impl<'de> Deserialize<'de> for Ben {
+    fn deserialize<D: de::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        deserializer.deserialize_any(BenVisitor)
+    }
+}
+
+struct BenVisitor;
+
+impl<'de> Visitor<'de> for BenVisitor {
+    type Value = Ben;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("a bencode value")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> std::result::Result<Ben, E> {
+        Ok(Ben::Number(v.into()))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> std::result::Result<Ben, E> {
+        Ok(Ben::Number(v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> std::result::Result<Ben, E> {
+        let v = i64::try_from(v).map_err(|_| E::invalid_value(Unexpected::Unsigned(v), &self))?;
+        Ok(Ben::Number(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> std::result::Result<Ben, E> {
+        self.visit_bytes(v.as_bytes())
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> std::result::Result<Ben, E> {
+        Ok(Ben::Bytes(v.to_vec()))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> std::result::Result<Ben, E> {
+        Ok(Ben::Bytes(v))
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<Ben, A::Error> {
+        let mut list = List::new();
+        while let Some(b) = seq.next_element()? {
+            list.push(b);
+        }
+        Ok(Ben::List(list))
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> std::result::Result<Ben, A::Error> {
+        let mut m = Map::new();
+        while let Some((k, v)) = map.next_entry::<serde_bytes::ByteBuf, Ben>()? {
+            m.insert(k.into_vec(), v);
+        }
+        Ok(Ben::Map(m))
+    }
+}
+
+fn unexpected(b: &Ben) -> Unexpected<'_> {
+    match b {
+        Ben::Bytes(b) => Unexpected::Bytes(b),
+        Ben::Number(i) => Unexpected::Signed(*i),
+        Ben::List(_) => Unexpected::Seq,
+        Ben::Map(_) => Unexpected::Map,
+    }
+}
+
+/// Deserializer reading from a borrowed [`Ben`].
+struct Deserializer<'de>(&'de Ben);
+
+impl<'de> Deserializer<'de> {
+    fn invalid_type(&self, exp: &dyn de::Expected) -> Error {
+        de::Error::invalid_type(unexpected(self.0), exp)
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            Ben::Bytes(b) => match std::str::from_utf8(b) {
+                Ok(s) => visitor.visit_borrowed_str(s),
+                Err(_) => visitor.visit_borrowed_bytes(b),
+            },
+            Ben::Number(i) => visitor.visit_i64(*i),
+            Ben::List(l) => visitor.visit_seq(SeqAccess {
+                iter: l.iter(),
+                index: 0,
+            }),
+            Ben::Map(m) => visitor.visit_map(MapAccess {
+                iter: m.iter(),
+                entry: None,
+            }),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            Ben::Number(0) => visitor.visit_bool(false),
+            Ben::Number(1) => visitor.visit_bool(true),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        Err(self.invalid_type(&visitor))
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        Err(self.invalid_type(&visitor))
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0.as_str() {
+            Some(s) => visitor.visit_borrowed_str(s),
+            None => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            Ben::Bytes(b) => visitor.visit_borrowed_bytes(b),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            Ben::List(_) => self.deserialize_any(visitor),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _: usize, visitor: V) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        _: usize,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            Ben::Map(_) => self.deserialize_any(visitor),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        _: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        _: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        match self.0 {
+            Ben::Bytes(variant) => visitor.visit_enum(EnumAccess {
+                variant,
+                value: None,
+            }),
+            Ben::Map(m) if m.len() == 1 => {
+                let (variant, value) = m.iter().next().expect("map with one entry");
+                visitor.visit_enum(EnumAccess {
+                    variant,
+                    value: Some(value),
+                })
+            }
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 unit unit_struct identifier
+    }
+}
+
+struct SeqAccess<'de> {
+    iter: std::slice::Iter<'de, Ben>,
+    index: usize,
+}
+
+impl<'de> de::SeqAccess<'de> for SeqAccess<'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>> {
+        let Some(b) = self.iter.next() else {
+            return Ok(None);
+        };
+        let index = self.index;
+        self.index += 1;
+        seed.deserialize(Deserializer(b))
+            .map(Some)
+            .map_err(|e| e.within(Segment::Index(index)))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct MapAccess<'de> {
+    iter: std::collections::btree_map::Iter<'de, Vec<u8>, Ben>,
+    entry: Option<(&'de Vec<u8>, &'de Ben)>,
+}
+
+impl<'de> de::MapAccess<'de> for MapAccess<'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        let Some((k, v)) = self.iter.next() else {
+            return Ok(None);
+        };
+        self.entry = Some((k, v));
+        seed.deserialize(KeyDeserializer(k)).map(Some)
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        let (k, v) = self
+            .entry
+            .take()
+            .ok_or_else(|| Error::from(ErrorKind::Message("value without key".into())))?;
+        seed.deserialize(Deserializer(v))
+            .map_err(|e| e.within(Segment::Key(k.clone())))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+/// Deserializer for dictionary keys, which are always byte strings.
+struct KeyDeserializer<'de>(&'de [u8]);
+
+impl<'de> de::Deserializer<'de> for KeyDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match std::str::from_utf8(self.0) {
+            Ok(s) => visitor.visit_borrowed_str(s),
+            Err(_) => visitor.visit_borrowed_bytes(self.0),
+        }
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_borrowed_bytes(self.0)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_bytes(visitor)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        option unit unit_struct newtype_struct seq tuple tuple_struct map struct
+        enum identifier ignored_any
+    }
+}
+
+struct EnumAccess<'de> {
+    variant: &'de [u8],
+    value: Option<&'de Ben>,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumAccess<'de> {
+    type Error = Error;
+    type Variant = VariantAccess<'de>;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant)> {
+        let variant = seed.deserialize(KeyDeserializer(self.variant))?;
+        Ok((variant, VariantAccess(self.value)))
+    }
+}
+
+struct VariantAccess<'de>(Option<&'de Ben>);
+
+impl<'de> VariantAccess<'de> {
+    fn value(self) -> Result<Deserializer<'de>> {
+        self.0
+            .map(Deserializer)
+            .ok_or_else(|| de::Error::invalid_type(Unexpected::UnitVariant, &"variant with data"))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for VariantAccess<'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        match self.0 {
+            None => Ok(()),
+            Some(b) => Err(de::Error::invalid_type(unexpected(b), &"unit variant")),
+        }
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        seed.deserialize(self.value()?)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _: usize, visitor: V) -> Result<V::Value> {
+        de::Deserializer::deserialize_seq(self.value()?, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        de::Deserializer::deserialize_map(self.value()?, visitor)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct File {
+        length: u32,
+        path: Vec<String>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Info {
+        name: String,
+        #[serde(with = "serde_bytes")]
+        pieces: Vec<u8>,
+        private: Option<bool>,
+        files: Vec<File>,
+        #[serde(flatten)]
+        extra: BTreeMap<String, Ben>,
+    }
+
+    #[test]
+    fn test_struct() {
+        let input =
+            b"d5:filesld6:lengthi3e4:pathl1:a1:beee4:name1:n6:pieces2:\xff\x006:source2:cie";
+        let info: Info = from_bytes(input).unwrap();
+        assert_eq!(info.name, "n");
+        assert_eq!(info.pieces, vec![0xff, 0x00]);
+        assert_eq!(info.private, None);
+        assert_eq!(
+            info.files,
+            vec![File {
+                length: 3,
+                path: vec!["a".into(), "b".into()]
+            }]
+        );
+        assert_eq!(info.extra["source"], "ci");
+    }
+
+    #[test]
+    fn test_error_location() {
+        let input = b"d5:filesld6:lengthi-3e4:pathl1:a1:beee4:name1:n6:pieces0:e";
+        let e = from_bytes::<Info>(input).unwrap_err();
+        assert_eq!(e.path.to_string(), "files[0].length");
+        assert_eq!(e.offset, Some(18));
+    }
+
+    #[test]
+    fn test_ben_round_trip() {
+        let b: Ben = "d1:ali1e2:xye1:bi-3ee".parse().unwrap();
+        assert_eq!(from_value::<Ben>(&b).unwrap(), b);
+    }
+}
@@ -1,90 +1,382 @@
-use crate::ben::{Ben, List, Map};
-use anyhow::{Context, Result};
-use std::{collections::HashMap, str::FromStr};
+use crate::ben::{
+    error::{Error, ErrorKind, Violation, Warning},
+    path::{Path, Segment},
+    Ben, BenRef, ListRef, MapRef,
+};
+use std::{ops::Range, str::FromStr};
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// How the decoder treats input that is valid but not canonical bencode.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Mode {
+    /// Accept non-canonical input and report it as warnings.
+    #[default]
+    Lenient,
+    /// Reject non-canonical input.
+    Strict,
+}
+
+/// Bounds on the shape of the input, for decoding data from untrusted sources.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Limits {
+    /// Deepest nesting of lists and dictionaries.
+    pub max_depth: usize,
+    /// Total number of values, including nested ones.
+    pub max_nodes: usize,
+    /// Longest byte string.
+    pub max_string_len: usize,
+    /// Most digits in an integer or a string length prefix.
+    pub max_integer_digits: usize,
+}
+
+impl Limits {
+    /// Limits for metadata received from trackers and peers.
+    pub fn untrusted() -> Self {
+        Self {
+            max_depth: 32,
+            max_nodes: 1 << 20,
+            max_string_len: 64 << 20,
+            ..Self::default()
+        }
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_depth: 256,
+            max_nodes: usize::MAX,
+            max_string_len: usize::MAX,
+            max_integer_digits: 32,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Options {
+    pub mode: Mode,
+    /// Record the byte range of every decoded node.
+    pub spans: bool,
+    pub limits: Limits,
+}
+
+impl Options {
+    /// Options for data received from trackers and peers.
+    pub fn untrusted() -> Self {
+        Self {
+            limits: Limits::untrusted(),
+            ..Self::default()
+        }
+    }
+}
+
+/// Byte ranges of decoded nodes in the input, in document order.
+#[derive(Debug, Default)]
+pub struct Spans(Vec<(Path, Range<usize>)>);
+
+impl Spans {
+    pub fn get(&self, path: &Path) -> Option<Range<usize>> {
+        self.0.iter().find(|s| s.0 == *path).map(|s| s.1.clone())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(Path, Range<usize>)> {
+        self.0.iter()
+    }
+}
+
+#[derive(Debug)]
+pub struct Decoded<V = Ben> {
+    pub value: V,
+    pub warnings: Vec<Warning>,
+    pub spans: Spans,
+}
 
 impl FromStr for Ben {
-    type Err = anyhow::Error;
+    type Err = Error;
     fn from_str(s: &str) -> Result<Self> {
-        let (r, b) = Self::decode(s)?;
-        anyhow::ensure!(r.is_empty(), "Unexpected remains: {r}");
-        Ok(b)
+        Self::try_from(s.as_bytes())
+    }
+}
+
+impl TryFrom<&[u8]> for Ben {
+    type Error = Error;
+    fn try_from(bytes: &[u8]) -> Result<Self> {
+        Self::decode_with(bytes, Options::default()).map(|d| d.value)
+    }
+}
+
+impl Ben {
+    pub fn decode_with(bytes: &[u8], options: Options) -> Result<Decoded> {
+        let d = BenRef::decode_with(bytes, options)?;
+        Ok(Decoded {
+            value: d.value.into(),
+            warnings: d.warnings,
+            spans: d.spans,
+        })
+    }
+
+    /// Decodes the first value in `bytes`, along with how many bytes it took.
+    pub fn decode_prefix(bytes: &[u8], options: Options) -> Result<(Decoded, usize)> {
+        let (d, used) = BenRef::decode_prefix(bytes, options)?;
+        let decoded = Decoded {
+            value: d.value.into(),
+            warnings: d.warnings,
+            spans: d.spans,
+        };
+        Ok((decoded, used))
+    }
+}
+
+impl<'a> BenRef<'a> {
+    /// Decodes `bytes` without copying any byte string out of it.
+    pub fn decode_with(bytes: &'a [u8], options: Options) -> Result<Decoded<BenRef<'a>>> {
+        let (decoded, used) = Self::decode_prefix(bytes, options)?;
+        if used < bytes.len() {
+            return Err(Error {
+                kind: ErrorKind::TrailingData(bytes.len() - used),
+                offset: Some(used),
+                path: Path::root(),
+            });
+        }
+        Ok(decoded)
+    }
+
+    /// Decodes the first value in `bytes`, along with how many bytes it took.
+    pub fn decode_prefix(
+        bytes: &'a [u8],
+        options: Options,
+    ) -> Result<(Decoded<BenRef<'a>>, usize)> {
+        let mut decoder = Decoder {
+            len: bytes.len(),
+            options,
+            warnings: vec![],
+            path: vec![],
+            spans: vec![],
+            nodes: 0,
+        };
+        let (r, value) = Self::decode(bytes, &mut decoder)?;
+        decoder.spans.sort_by_key(|s| s.1.start);
+        let decoded = Decoded {
+            value,
+            warnings: decoder.warnings,
+            spans: Spans(decoder.spans),
+        };
+        Ok((decoded, bytes.len() - r.len()))
+    }
+}
+
+struct Decoder {
+    len: usize,
+    options: Options,
+    warnings: Vec<Warning>,
+    path: Vec<Segment>,
+    spans: Vec<(Path, Range<usize>)>,
+    nodes: usize,
+}
+
+impl Decoder {
+    fn offset(&self, at: &[u8]) -> usize {
+        self.len - at.len()
+    }
+
+    fn error(&self, kind: ErrorKind, at: &[u8]) -> Error {
+        Error {
+            kind,
+            offset: Some(self.offset(at)),
+            path: Path::from(self.path.as_slice()),
+        }
+    }
+
+    fn eof(&self) -> Error {
+        self.error(ErrorKind::UnexpectedEof, &[])
+    }
+
+    fn check_digits(&self, count: usize, at: &[u8]) -> Result<()> {
+        let max = self.options.limits.max_integer_digits;
+        match count > max {
+            true => Err(self.error(ErrorKind::TooManyDigits(max), at)),
+            false => Ok(()),
+        }
+    }
+
+    /// Accounts for a new node, failing once `max_nodes` or `max_depth` are exceeded.
+    fn enter(&mut self, nested: bool, at: &[u8]) -> Result<()> {
+        let limits = self.options.limits;
+        self.nodes += 1;
+        if self.nodes > limits.max_nodes {
+            return Err(self.error(ErrorKind::TooManyNodes(limits.max_nodes), at));
+        }
+        if nested && self.path.len() >= limits.max_depth {
+            return Err(self.error(ErrorKind::TooDeep(limits.max_depth), at));
+        }
+        Ok(())
+    }
+
+    fn report(&mut self, violation: Violation, at: &[u8]) -> Result<()> {
+        match self.options.mode {
+            Mode::Strict => Err(self.error(violation.into(), at)),
+            Mode::Lenient => {
+                self.warnings.push(Warning {
+                    violation,
+                    offset: self.offset(at),
+                    path: Path::from(self.path.as_slice()),
+                });
+                Ok(())
+            }
+        }
     }
 }
 
-trait Decode
+trait Decode<'a>
 where
     Self: Sized,
 {
-    fn decode(input: &'_ str) -> Result<(&'_ str, Self)>;
+    fn decode(input: &'a [u8], d: &mut Decoder) -> Result<(&'a [u8], Self)>;
+}
+
+fn digits(input: &[u8]) -> usize {
+    input.iter().take_while(|b| b.is_ascii_digit()).count()
 }
 
-impl Decode for String {
-    fn decode(input: &str) -> Result<(&str, String)> {
-        let (count, input) = input.split_once(':').context("invalid string format")?;
-        let count: usize = count.parse()?;
-        anyhow::ensure!(count <= input.len(), "invalid ben string lenght: {count}");
-        let (input, out) = input.split_at(count);
-        Ok((out, input.to_owned()))
+impl<'a> Decode<'a> for &'a [u8] {
+    fn decode(input: &'a [u8], d: &mut Decoder) -> Result<(&'a [u8], &'a [u8])> {
+        let (count, rest) = input.split_at(digits(input));
+        d.check_digits(count.len(), input)?;
+        match rest.first() {
+            None => return Err(d.eof()),
+            Some(b':') if !count.is_empty() => {}
+            Some(_) => return Err(d.error(ErrorKind::InvalidLength, rest)),
+        }
+        if count.len() > 1 && count[0] == b'0' {
+            d.report(Violation::LengthLeadingZero, input)?;
+        }
+        let count: usize = std::str::from_utf8(count)
+            .ok()
+            .and_then(|c| c.parse().ok())
+            .ok_or_else(|| d.error(ErrorKind::InvalidLength, input))?;
+        let max = d.options.limits.max_string_len;
+        if count > max {
+            return Err(d.error(ErrorKind::StringTooLong(max), input));
+        }
+        let rest = &rest[1..];
+        if count > rest.len() {
+            return Err(d.eof());
+        }
+        let (input, out) = rest.split_at(count);
+        Ok((out, input))
     }
 }
 
-impl Decode for i64 {
-    fn decode(input: &str) -> Result<(&str, i64)> {
-        let input = input.strip_prefix('i').context("invalid ben integer")?;
-        let (input, out) = input.split_once('e').context("invalid ben integer")?;
-        let num = input.parse()?;
-        Ok((out, num))
+impl<'a> Decode<'a> for i64 {
+    fn decode(input: &'a [u8], d: &mut Decoder) -> Result<(&'a [u8], i64)> {
+        let body = &input[1..];
+        let sign = match body.first() {
+            Some(s @ (b'-' | b'+')) => Some(*s),
+            _ => None,
+        };
+        let start = sign.map_or(0, |_| 1);
+        let end = start + digits(&body[start..]);
+        d.check_digits(end - start, input)?;
+        match body.get(end) {
+            None => return Err(d.eof()),
+            Some(b'e') if end == start => return Err(d.error(ErrorKind::EmptyInteger, input)),
+            Some(b'e') => {}
+            Some(_) => return Err(d.error(ErrorKind::InvalidInteger, &body[end..])),
+        }
+        if sign == Some(b'+') {
+            d.report(Violation::PlusSign, input)?;
+        }
+        if end - start > 1 && body[start] == b'0' {
+            d.report(Violation::LeadingZero, input)?;
+        }
+        let num: i64 = std::str::from_utf8(&body[..end])
+            .ok()
+            .and_then(|n| n.parse().ok())
+            .ok_or_else(|| d.error(ErrorKind::IntegerOverflow, input))?;
+        if sign == Some(b'-') && num == 0 {
+            d.report(Violation::NegativeZero, input)?;
+        }
+        Ok((&body[end + 1..], num))
     }
 }
 
-impl Decode for Vec<Ben> {
-    fn decode(input: &str) -> Result<(&str, Vec<Ben>)> {
-        let input = input.strip_prefix('l').context("invalid ben list start")?;
-        let mut input = input;
+impl<'a> Decode<'a> for ListRef<'a> {
+    fn decode(input: &'a [u8], d: &mut Decoder) -> Result<(&'a [u8], ListRef<'a>)> {
+        let mut input = &input[1..];
         let mut v = vec![];
-        while !input.starts_with('e') && !input.is_empty() {
+        loop {
+            match input.first() {
+                None => return Err(d.eof()),
+                Some(b'e') => break,
+                Some(_) => {}
+            }
             let ben;
-            (input, ben) = Ben::decode(input)?;
+            d.path.push(Segment::Index(v.len()));
+            (input, ben) = BenRef::decode(input, d)?;
+            d.path.pop();
             v.push(ben);
         }
-        anyhow::ensure!(!input.is_empty(), "Invalid end of list");
-        let input = &input[1..];
-        Ok((input, v))
+        Ok((&input[1..], v))
     }
 }
 
-impl Decode for Map {
-    fn decode(input: &str) -> Result<(&str, HashMap<String, Ben>)> {
-        let mut input = input.strip_prefix('d').context("invalid ben dict start")?;
-        let mut map = HashMap::<String, Ben>::new();
-        while !input.starts_with('e') && !input.is_empty() {
-            let key;
+impl<'a> Decode<'a> for MapRef<'a> {
+    fn decode(input: &'a [u8], d: &mut Decoder) -> Result<(&'a [u8], MapRef<'a>)> {
+        let mut input = &input[1..];
+        let mut map = MapRef::new();
+        let mut last: Option<&[u8]> = None;
+        loop {
+            match input.first() {
+                None => return Err(d.eof()),
+                Some(b'e') => break,
+                Some(c) if !c.is_ascii_digit() => {
+                    return Err(d.error(ErrorKind::KeyNotString, input))
+                }
+                Some(_) => {}
+            }
+            let at = input;
+            let key: &[u8];
             let ben;
-            (input, key) = String::decode(input)?;
-            (input, ben) = Ben::decode(input)?;
+            (input, key) = <&[u8]>::decode(input, d)?;
+            if map.contains_key(key) {
+                d.report(Violation::DuplicateKey(key.to_vec()), at)?;
+            } else if last.is_some_and(|l| key < l) {
+                d.report(Violation::UnsortedKey(key.to_vec()), at)?;
+            }
+            d.path.push(Segment::Key(key.to_vec()));
+            (input, ben) = BenRef::decode(input, d)?;
+            d.path.pop();
             map.insert(key, ben);
+            last = Some(key);
         }
-        anyhow::ensure!(!input.is_empty(), "Invalid end of dict");
-        let input = &input[1..];
-        Ok((input, map))
+        Ok((&input[1..], map))
     }
 }
 
-impl Decode for Ben {
-    fn decode(input: &str) -> Result<(&str, Ben)> {
-        match input.chars().next().context("Input is empty")? {
-            c if c.is_ascii_digit() => String::decode(input).map(|t| (t.0, Ben::String(t.1))),
-            'i' => i64::decode(input).map(|t| (t.0, Ben::Number(t.1))),
-            'l' => List::decode(input).map(|t| (t.0, Ben::List(t.1))),
-            'd' => Map::decode(input).map(|t| (t.0, Ben::Map(t.1))),
-            _ => anyhow::bail!("Unknown encoding: {input}"),
+impl<'a> Decode<'a> for BenRef<'a> {
+    fn decode(input: &'a [u8], d: &mut Decoder) -> Result<(&'a [u8], BenRef<'a>)> {
+        let first = input.first().ok_or_else(|| d.eof())?;
+        d.enter(matches!(first, b'l' | b'd'), input)?;
+        let (rest, ben) = match first {
+            c if c.is_ascii_digit() => <&[u8]>::decode(input, d).map(|t| (t.0, BenRef::Bytes(t.1))),
+            b'i' => i64::decode(input, d).map(|t| (t.0, BenRef::Number(t.1))),
+            b'l' => ListRef::decode(input, d).map(|t| (t.0, BenRef::List(t.1))),
+            b'd' => MapRef::decode(input, d).map(|t| (t.0, BenRef::Map(t.1))),
+            c => Err(d.error(ErrorKind::UnexpectedByte(*c), input)),
+        }?;
+        if d.options.spans {
+            let span = d.offset(input)..d.offset(rest);
+            d.spans.push((Path::from(d.path.as_slice()), span));
         }
+        Ok((rest, ben))
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::ben::Map;
 
     #[test]
     fn test_string() {
@@ -92,6 +384,12 @@ mod test {
         assert_eq!(b, "hernan.rs");
     }
 
+    #[test]
+    fn test_binary_string() {
+        let b = Ben::try_from(&b"4:\xde\xad\xbe\xef"[..]).unwrap();
+        assert_eq!(b, Ben::Bytes(vec![0xde, 0xad, 0xbe, 0xef]));
+    }
+
     #[test]
     fn test_integer() {
         let b: Ben = "i42e".parse().unwrap();
@@ -99,6 +397,9 @@ mod test {
 
         let b: Ben = "i-42e".parse().unwrap();
         assert_eq!(b, -42);
+
+        assert!("ie".parse::<Ben>().is_err());
+        assert!("i-e".parse::<Ben>().is_err());
     }
 
     #[test]
@@ -112,8 +413,131 @@ mod test {
     fn test_dict() {
         let b: Ben = "d3:foo3:bar6:hernani82ee".parse().unwrap();
         let mut m = Map::new();
-        m.insert("foo".into(), Ben::String("bar".into()));
+        m.insert("foo".into(), Ben::Bytes("bar".into()));
         m.insert("hernan".into(), Ben::Number(82));
         assert_eq!(b, Ben::Map(m));
     }
+
+    #[test]
+    fn test_error_location() {
+        let e = "d4:infod5:filesli1ei2xeeee".parse::<Ben>().unwrap_err();
+        assert_eq!(e.kind, ErrorKind::InvalidInteger);
+        assert_eq!(e.offset, Some(21));
+        assert_eq!(e.path.to_string(), "info.files[1]");
+        assert_eq!(
+            e.to_string(),
+            "invalid integer at offset 21 in info.files[1]"
+        );
+
+        let e = "l5:abc".parse::<Ben>().unwrap_err();
+        assert_eq!((e.kind, e.offset), (ErrorKind::UnexpectedEof, Some(6)));
+
+        let e = "i1ei2e".parse::<Ben>().unwrap_err();
+        assert_eq!((e.kind, e.offset), (ErrorKind::TrailingData(3), Some(3)));
+    }
+
+    #[test]
+    fn test_spans() {
+        let input = b"d1:ali1ee1:bd1:ci2eee";
+        let options = Options {
+            spans: true,
+            ..Default::default()
+        };
+        let d = Ben::decode_with(input, options).unwrap();
+        let b = d.spans.get(&Path::root().key("b")).unwrap();
+        assert_eq!(&input[b], b"d1:ci2ee");
+        let a = d.spans.get(&Path::root().key("a").index(0)).unwrap();
+        assert_eq!(&input[a], b"i1e");
+        assert_eq!(d.spans.iter().next().unwrap().1, 0..input.len());
+    }
+
+    fn limited(limits: Limits) -> Options {
+        Options {
+            limits,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_limits() {
+        let limits = Limits {
+            max_depth: 2,
+            max_nodes: 4,
+            max_string_len: 3,
+            max_integer_digits: 3,
+        };
+        let cases: [(&[u8], ErrorKind); 5] = [
+            (b"lllee", ErrorKind::TooDeep(2)),
+            (b"li1ei2ei3ei4ee", ErrorKind::TooManyNodes(4)),
+            (b"4:spam", ErrorKind::StringTooLong(3)),
+            (b"i1234e", ErrorKind::TooManyDigits(3)),
+            (b"0001:", ErrorKind::TooManyDigits(3)),
+        ];
+        for (input, kind) in cases {
+            let e = Ben::decode_with(input, limited(limits)).unwrap_err();
+            assert_eq!(e.kind, kind);
+        }
+        assert!(Ben::decode_with(b"lli1ee3:abce", limited(limits)).is_ok());
+    }
+
+    #[test]
+    fn test_deep_nesting() {
+        let input = [vec![b'l'; 100_000], vec![b'e'; 100_000]].concat();
+        let e = Ben::decode_with(&input, Options::default()).unwrap_err();
+        assert_eq!(e.kind, ErrorKind::TooDeep(256));
+    }
+
+    fn strict() -> Options {
+        Options {
+            mode: Mode::Strict,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_strict() {
+        for input in [
+            "i-0e",
+            "i03e",
+            "i+3e",
+            "03:abc",
+            "d1:bi1e1:ai2ee",
+            "d1:ai1e1:ai2ee",
+        ] {
+            let res = Ben::decode_with(input.as_bytes(), strict());
+            assert!(res.is_err(), "{input} should be rejected");
+        }
+        assert!(Ben::decode_with(b"d1:ai-1e1:bi0ee", strict()).is_ok());
+    }
+
+    #[test]
+    fn test_lenient_warnings() {
+        let d = Ben::decode_with(b"d1:bi03e1:ai-0e1:ai1ee", Options::default()).unwrap();
+        let violations: Vec<_> = d.warnings.iter().map(|w| w.violation.clone()).collect();
+        assert_eq!(
+            violations,
+            vec![
+                Violation::LeadingZero,
+                Violation::UnsortedKey(b"a".to_vec()),
+                Violation::NegativeZero,
+                Violation::DuplicateKey(b"a".to_vec()),
+            ]
+        );
+        assert_eq!(d.warnings[0].path.to_string(), "b");
+        assert_eq!(d.warnings[1].offset, 8);
+        assert_eq!(d.value.to_string(), r#"{"a":1,"b":3}"#);
+    }
+
+    #[test]
+    fn test_torrent_file() {
+        let data = std::fs::read("sample.torrent").unwrap();
+        let d = Ben::decode_with(&data, strict()).unwrap();
+        let Ben::Map(m) = d.value else {
+            panic!("torrent is not a dictionary")
+        };
+        let Some(Ben::Map(info)) = m.get(&b"info"[..]) else {
+            panic!("info dictionary not found")
+        };
+        assert!(info[&b"pieces"[..]].as_str().is_none());
+    }
 }
@@ -0,0 +1,130 @@
+use crate::ben::{
+    path::{Path, Segment},
+    Ben,
+};
+use std::{
+    collections::BTreeSet,
+    fmt::{Display, Formatter, Result},
+};
+
+/// Difference between two bencode trees at a given node.
+#[derive(Debug, PartialEq)]
+pub enum Change<'a> {
+    Added(Path, &'a Ben),
+    Removed(Path, &'a Ben),
+    Changed(Path, &'a Ben, &'a Ben),
+}
+
+impl Ben {
+    /// Changes turning `self` into `other`, in document order.
+    pub fn diff<'a>(&'a self, other: &'a Ben) -> Vec<Change<'a>> {
+        let mut changes = vec![];
+        diff(Path::root(), self, other, &mut changes);
+        changes
+    }
+}
+
+fn diff<'a>(path: Path, a: &'a Ben, b: &'a Ben, out: &mut Vec<Change<'a>>) {
+    match (a, b) {
+        (Ben::Map(x), Ben::Map(y)) => {
+            let keys: BTreeSet<_> = x.keys().chain(y.keys()).collect();
+            for k in keys {
+                let path = path.clone().child(Segment::Key(k.clone()));
+                match (x.get(k), y.get(k)) {
+                    (Some(a), Some(b)) => diff(path, a, b, out),
+                    (Some(a), None) => out.push(Change::Removed(path, a)),
+                    (None, Some(b)) => out.push(Change::Added(path, b)),
+                    (None, None) => {}
+                }
+            }
+        }
+        (Ben::List(x), Ben::List(y)) => {
+            for i in 0..x.len().max(y.len()) {
+                let path = path.clone().child(Segment::Index(i));
+                match (x.get(i), y.get(i)) {
+                    (Some(a), Some(b)) => diff(path, a, b, out),
+                    (Some(a), None) => out.push(Change::Removed(path, a)),
+                    (None, Some(b)) => out.push(Change::Added(path, b)),
+                    (None, None) => {}
+                }
+            }
+        }
+        (a, b) if a != b => out.push(Change::Changed(path, a, b)),
+        _ => {}
+    }
+}
+
+/// One-line description of a node.
+struct Summary<'a>(&'a Ben);
+
+impl Display for Summary<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self.0 {
+            Ben::List(l) => write!(f, "[{} items]", l.len()),
+            Ben::Map(m) => write!(f, "{{{} keys}}", m.len()),
+            b => b.pretty().fmt(f),
+        }
+    }
+}
+
+struct Location<'a>(&'a Path);
+
+impl Display for Location<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self.0.is_root() {
+            true => write!(f, "."),
+            false => self.0.fmt(f),
+        }
+    }
+}
+
+impl Display for Change<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Change::Added(p, b) => write!(f, "+ {}: {}", Location(p), Summary(b)),
+            Change::Removed(p, a) => write!(f, "- {}: {}", Location(p), Summary(a)),
+            Change::Changed(p, a, b) => {
+                write!(f, "~ {}: {} -> {}", Location(p), Summary(a), Summary(b))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ben(json: serde_json::Value) -> Ben {
+        Ben::try_from(&json).unwrap()
+    }
+
+    #[test]
+    fn test_changes() {
+        let a = ben(serde_json::json!({
+            "announce": "http://a",
+            "info": {"name": "x", "private": 1, "files": [{"length": 1}, {"length": 2}]},
+        }));
+        let b = ben(serde_json::json!({
+            "announce": "http://a",
+            "info": {"name": "y", "source": "z", "files": [{"length": 1}]},
+        }));
+        let changes: Vec<_> = a.diff(&b).iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            changes,
+            vec![
+                "- info.files[1]: {1 keys}",
+                r#"~ info.name: "x" (1 bytes) -> "y" (1 bytes)"#,
+                "- info.private: 1",
+                r#"+ info.source: "z" (1 bytes)"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_identical() {
+        let a = ben(serde_json::json!({"a": [1, 2, {"b": "c"}]}));
+        assert!(a.diff(&a.clone()).is_empty());
+        let changes = a.diff(&Ben::Number(1));
+        assert_eq!(changes[0].to_string(), "~ .: {1 keys} -> 1");
+    }
+}
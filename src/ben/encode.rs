@@ -0,0 +1,87 @@
+use crate::ben::{Ben, List, Map};
+use std::io::Write;
+
+impl Ben {
+    /// Canonical bencode representation of this value.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = vec![];
+        self.encode(&mut buf);
+        buf
+    }
+}
+
+trait Encode {
+    fn encode(&self, buf: &mut Vec<u8>);
+}
+
+impl Encode for [u8] {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        let _ = write!(buf, "{}:", self.len());
+        buf.extend_from_slice(self);
+    }
+}
+
+impl Encode for i64 {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        let _ = write!(buf, "i{self}e");
+    }
+}
+
+impl Encode for List {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.push(b'l');
+        self.iter().for_each(|b| b.encode(buf));
+        buf.push(b'e');
+    }
+}
+
+impl Encode for Map {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.push(b'd');
+        for (key, value) in self {
+            key.encode(buf);
+            value.encode(buf);
+        }
+        buf.push(b'e');
+    }
+}
+
+impl Encode for Ben {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            Ben::Bytes(b) => b.encode(buf),
+            Ben::Number(i) => i.encode(buf),
+            Ben::List(l) => l.encode(buf),
+            Ben::Map(m) => m.encode(buf),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_scalars() {
+        assert_eq!(Ben::Bytes("spam".into()).to_bytes(), b"4:spam");
+        assert_eq!(Ben::Bytes(vec![]).to_bytes(), b"0:");
+        assert_eq!(Ben::Number(0).to_bytes(), b"i0e");
+        assert_eq!(Ben::Number(-42).to_bytes(), b"i-42e");
+    }
+
+    #[test]
+    fn test_canonical_keys() {
+        let mut m = Map::new();
+        m.insert("zoo".into(), Ben::Number(1));
+        m.insert("ab".into(), Ben::Number(2));
+        m.insert("a".into(), Ben::List(vec![Ben::Bytes(vec![0xff])]));
+        assert_eq!(Ben::Map(m).to_bytes(), b"d1:al1:\xffe2:abi2e3:zooi1ee");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let data = std::fs::read("sample.torrent").unwrap();
+        let b = Ben::try_from(data.as_slice()).unwrap();
+        assert_eq!(b.to_bytes(), data);
+    }
+}
@@ -0,0 +1,128 @@
+use super::{path::Segment, Path};
+use std::fmt::Display;
+
+/// Valid bencode that is not in its canonical form.
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum Violation {
+    #[error("integer with leading zeros")]
+    LeadingZero,
+    #[error("negative zero integer")]
+    NegativeZero,
+    #[error("integer with explicit plus sign")]
+    PlusSign,
+    #[error("string length with leading zeros")]
+    LengthLeadingZero,
+    #[error("dictionary key {:?} is not sorted", String::from_utf8_lossy(.0))]
+    UnsortedKey(Vec<u8>),
+    #[error("duplicate dictionary key {:?}", String::from_utf8_lossy(.0))]
+    DuplicateKey(Vec<u8>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum ErrorKind {
+    #[error("unexpected end of input")]
+    UnexpectedEof,
+    #[error("unexpected byte {:?}", *.0 as char)]
+    UnexpectedByte(u8),
+    #[error("invalid string length")]
+    InvalidLength,
+    #[error("empty integer")]
+    EmptyInteger,
+    #[error("invalid integer")]
+    InvalidInteger,
+    #[error("integer out of range")]
+    IntegerOverflow,
+    #[error("dictionary key is not a string")]
+    KeyNotString,
+    #[error("nesting deeper than {0} levels")]
+    TooDeep(usize),
+    #[error("more than {0} values")]
+    TooManyNodes(usize),
+    #[error("string longer than {0} bytes")]
+    StringTooLong(usize),
+    #[error("number with more than {0} digits")]
+    TooManyDigits(usize),
+    #[error("{0} trailing bytes")]
+    TrailingData(usize),
+    #[error(transparent)]
+    NonCanonical(#[from] Violation),
+    #[error("{0}")]
+    Message(String),
+}
+
+/// Failure located by path to the failing node and, when it comes from
+/// parsing input, by byte offset.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Error {
+    pub kind: ErrorKind,
+    pub offset: Option<usize>,
+    pub path: Path,
+}
+
+impl Error {
+    /// Same error, seen from the parent of the node it happened in.
+    pub(crate) fn within(mut self, segment: Segment) -> Self {
+        self.path = self.path.within(segment);
+        self
+    }
+
+    /// Whether the input ended early, so more bytes could still make it valid.
+    pub fn is_incomplete(&self) -> bool {
+        self.kind == ErrorKind::UnexpectedEof
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if let Some(offset) = self.offset {
+            write!(f, " at offset {offset}")?;
+        }
+        if !self.path.is_root() {
+            write!(f, " in {}", self.path)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Self {
+        Self {
+            kind,
+            offset: None,
+            path: Path::root(),
+        }
+    }
+}
+
+impl serde::de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        ErrorKind::Message(msg.to_string()).into()
+    }
+}
+
+impl serde::ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        ErrorKind::Message(msg.to_string()).into()
+    }
+}
+
+/// Non-canonical input accepted in lenient mode.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Warning {
+    pub violation: Violation,
+    pub offset: usize,
+    pub path: Path,
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at offset {}", self.violation, self.offset)?;
+        if !self.path.is_root() {
+            write!(f, " in {}", self.path)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,89 @@
+use crate::ben::{error::Error, Ben, BenRef, Options};
+
+/// Decoder fed with bytes as they arrive, e.g. from a tracker response body.
+///
+/// This is not a resumable decoder: each attempt restarts from the first
+/// buffered byte, only skipping attempts until new bytes arrive. A value fed
+/// in N chunks may therefore cost N full decodes, which is fine for small
+/// bodies such as tracker responses but not for large, finely chunked input.
+#[derive(Debug, Default)]
+pub struct Incremental {
+    buffer: Vec<u8>,
+    options: Options,
+    /// Buffered length when the last attempt ran out of input.
+    incomplete: Option<usize>,
+}
+
+impl Incremental {
+    pub fn new(options: Options) -> Self {
+        Self {
+            buffer: vec![],
+            options,
+            incomplete: None,
+        }
+    }
+
+    pub fn feed(&mut self, bytes: impl AsRef<[u8]>) {
+        self.buffer.extend_from_slice(bytes.as_ref());
+    }
+
+    /// Next complete value, or `None` if more bytes are needed to finish it.
+    ///
+    /// Warnings are dropped; use [`Mode::Strict`](crate::ben::Mode::Strict) in the
+    /// options to reject non-canonical input instead.
+    pub fn next_value(&mut self) -> Result<Option<Ben>, Error> {
+        if self.incomplete.is_some_and(|len| self.buffer.len() <= len) {
+            return Ok(None);
+        }
+        match BenRef::decode_prefix(&self.buffer, self.options) {
+            Ok((decoded, used)) => {
+                let value = decoded.value.into();
+                self.buffer.drain(..used);
+                self.incomplete = None;
+                Ok(Some(value))
+            }
+            Err(e) if e.is_incomplete() => {
+                self.incomplete = Some(self.buffer.len());
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ben::error::ErrorKind;
+
+    #[test]
+    fn test_byte_by_byte() {
+        let input = b"d3:foo3:bar4:listli1ei-2eee";
+        let mut inc = Incremental::default();
+        for (i, b) in input.iter().enumerate() {
+            inc.feed([*b]);
+            let value = inc.next_value().unwrap();
+            assert_eq!(value.is_some(), i == input.len() - 1);
+        }
+        assert_eq!(inc.next_value().unwrap(), None);
+    }
+
+    #[test]
+    fn test_consecutive_values() {
+        let mut inc = Incremental::default();
+        inc.feed("i1e4:sp");
+        assert_eq!(inc.next_value().unwrap(), Some(Ben::Number(1)));
+        assert_eq!(inc.next_value().unwrap(), None);
+        assert_eq!(inc.next_value().unwrap(), None);
+        inc.feed("am");
+        assert_eq!(inc.next_value().unwrap().unwrap(), "spam");
+    }
+
+    #[test]
+    fn test_malformed() {
+        let mut inc = Incremental::default();
+        inc.feed("li1ei2x");
+        let e = inc.next_value().unwrap_err();
+        assert_eq!((e.kind, e.offset), (ErrorKind::InvalidInteger, Some(6)));
+    }
+}
@@ -0,0 +1,145 @@
+//! Lossless mapping between bencode and JSON.
+//!
+//! - Byte strings that are valid UTF-8 become JSON strings; any other byte
+//!   string becomes `{"$hex": "<hex digits>"}`.
+//! - Integers become JSON numbers, lists become arrays, dictionaries objects.
+//! - Dictionary keys are kept as they are, except that keys starting with `$`
+//!   get one more `$` in front, and keys that are not valid UTF-8 become
+//!   `"$hex:<hex digits>"`. Objects with a single `$hex` key are therefore
+//!   never dictionaries.
+//!
+//! Converting a value to JSON and back yields the same value, so a canonical
+//! file re-encodes to the very same bytes.
+use crate::ben::{
+    error::{Error, ErrorKind},
+    path::Segment,
+    Ben, Map,
+};
+use serde_json::Value;
+
+const HEX_TAG: &str = "$hex";
+const ESCAPE: char = '$';
+
+fn json_key(key: &[u8]) -> String {
+    match std::str::from_utf8(key) {
+        Ok(s) if s.starts_with(ESCAPE) => format!("{ESCAPE}{s}"),
+        Ok(s) => s.to_owned(),
+        Err(_) => format!("{HEX_TAG}:{}", hex::encode(key)),
+    }
+}
+
+fn ben_key(key: &str) -> Result<Vec<u8>, Error> {
+    if let Some(digits) = key.strip_prefix(HEX_TAG).and_then(|k| k.strip_prefix(':')) {
+        return unhex(digits);
+    }
+    match key.strip_prefix(ESCAPE) {
+        Some(k) if k.starts_with(ESCAPE) => Ok(k.into()),
+        Some(_) => Err(ErrorKind::Message(format!("unknown escaped key {key:?}")).into()),
+        None => Ok(key.into()),
+    }
+}
+
+fn unhex(digits: &str) -> Result<Vec<u8>, Error> {
+    hex::decode(digits).map_err(|e| ErrorKind::Message(format!("invalid hex string: {e}")).into())
+}
+
+impl From<&Ben> for Value {
+    fn from(value: &Ben) -> Self {
+        match value {
+            Ben::Bytes(b) => match std::str::from_utf8(b) {
+                Ok(s) => Value::String(s.to_owned()),
+                Err(_) => serde_json::json!({ HEX_TAG: hex::encode(b) }),
+            },
+            Ben::Number(i) => Value::from(*i),
+            Ben::List(v) => Value::Array(v.iter().map(Value::from).collect()),
+            Ben::Map(m) => {
+                let map = m.iter().map(|x| (json_key(x.0), Self::from(x.1))).fold(
+                    serde_json::Map::new(),
+                    |mut map, x| {
+                        map.insert(x.0, x.1);
+                        map
+                    },
+                );
+                Value::Object(map)
+            }
+        }
+    }
+}
+
+impl TryFrom<&Value> for Ben {
+    type Error = Error;
+    fn try_from(value: &Value) -> Result<Self, Error> {
+        let unsupported =
+            |what| ErrorKind::Message(format!("JSON {what} has no bencode equivalent"));
+        match value {
+            Value::String(s) => Ok(Ben::Bytes(s.as_bytes().to_vec())),
+            Value::Number(n) => n
+                .as_i64()
+                .map(Ben::Number)
+                .ok_or_else(|| unsupported("number").into()),
+            Value::Array(a) => a
+                .iter()
+                .enumerate()
+                .map(|(i, v)| Ben::try_from(v).map_err(|e| e.within(Segment::Index(i))))
+                .collect::<Result<_, _>>()
+                .map(Ben::List),
+            Value::Object(o) => match o.get(HEX_TAG) {
+                Some(Value::String(digits)) if o.len() == 1 => unhex(digits).map(Ben::Bytes),
+                Some(_) if o.len() == 1 => Err(unsupported("non-string $hex").into()),
+                _ => o
+                    .iter()
+                    .map(|(k, v)| {
+                        let key = ben_key(k)?;
+                        let value =
+                            Ben::try_from(v).map_err(|e| e.within(Segment::Key(key.clone())))?;
+                        Ok((key, value))
+                    })
+                    .collect::<Result<Map, Error>>()
+                    .map(Ben::Map),
+            },
+            Value::Bool(_) => Err(unsupported("boolean").into()),
+            Value::Null => Err(unsupported("null").into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn round_trip(b: &Ben) -> Ben {
+        let json = Value::from(b).to_string();
+        let value: Value = serde_json::from_str(&json).unwrap();
+        Ben::try_from(&value).unwrap()
+    }
+
+    #[test]
+    fn test_escaped_keys() {
+        let mut m = Map::new();
+        m.insert("$hex".into(), Ben::Bytes("not binary".into()));
+        m.insert("$$".into(), Ben::Number(1));
+        m.insert(vec![0xff, 0x00], Ben::Bytes(vec![0xfe]));
+        let b = Ben::Map(m);
+        assert_eq!(
+            Value::from(&b).to_string(),
+            r#"{"$$$":1,"$$hex":"not binary","$hex:ff00":{"$hex":"fe"}}"#
+        );
+        assert_eq!(round_trip(&b), b);
+    }
+
+    #[test]
+    fn test_torrent_round_trip() {
+        let data = std::fs::read("sample.torrent").unwrap();
+        let b = Ben::try_from(data.as_slice()).unwrap();
+        assert_eq!(round_trip(&b).to_bytes(), data);
+    }
+
+    #[test]
+    fn test_unsupported() {
+        let value = serde_json::json!({"a": [1, 2.5]});
+        let e = Ben::try_from(&value).unwrap_err();
+        assert_eq!(e.path.to_string(), "a[1]");
+        assert!(Ben::try_from(&serde_json::json!({"$x": 1})).is_err());
+        assert!(Ben::try_from(&serde_json::json!(null)).is_err());
+    }
+}
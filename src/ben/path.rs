@@ -0,0 +1,79 @@
+use std::fmt::Display;
+
+/// One step from a node to one of its children.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Segment {
+    Key(Vec<u8>),
+    Index(usize),
+}
+
+/// Location of a node within a bencode tree, e.g. `info.files[3].length`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Path(Vec<Segment>);
+
+impl Path {
+    pub fn root() -> Self {
+        Self::default()
+    }
+
+    pub fn key(mut self, key: impl AsRef<[u8]>) -> Self {
+        self.0.push(Segment::Key(key.as_ref().to_vec()));
+        self
+    }
+
+    pub fn index(mut self, index: usize) -> Self {
+        self.0.push(Segment::Index(index));
+        self
+    }
+
+    pub fn child(mut self, segment: Segment) -> Self {
+        self.0.push(segment);
+        self
+    }
+
+    /// This path, seen from one level up in the tree.
+    pub fn within(mut self, segment: Segment) -> Self {
+        self.0.insert(0, segment);
+        self
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.0
+    }
+}
+
+impl From<&[Segment]> for Path {
+    fn from(segments: &[Segment]) -> Self {
+        Self(segments.to_vec())
+    }
+}
+
+impl Display for Path {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, s) in self.0.iter().enumerate() {
+            match s {
+                Segment::Key(k) if i == 0 => write!(f, "{}", String::from_utf8_lossy(k))?,
+                Segment::Key(k) => write!(f, ".{}", String::from_utf8_lossy(k))?,
+                Segment::Index(n) => write!(f, "[{n}]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let p = Path::root().key("info").key("files").index(3).key("length");
+        assert_eq!(p.to_string(), "info.files[3].length");
+        assert_eq!(Path::root().index(0).index(1).to_string(), "[0][1]");
+        assert!(Path::root().is_root());
+    }
+}
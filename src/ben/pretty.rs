@@ -0,0 +1,137 @@
+use crate::ben::Ben;
+use std::fmt::{Display, Formatter, Result};
+
+/// Indented, human-oriented rendering of a bencode tree.
+///
+/// Byte strings are annotated with their length, binary ones are shown as a
+/// hex preview, and long strings and lists are truncated.
+pub struct Pretty<'a> {
+    value: &'a Ben,
+    max_text: usize,
+    max_hex: usize,
+    max_items: usize,
+}
+
+const INDENT: &str = "  ";
+
+impl Ben {
+    pub fn pretty(&self) -> Pretty<'_> {
+        Pretty {
+            value: self,
+            max_text: 80,
+            max_hex: 20,
+            max_items: 32,
+        }
+    }
+}
+
+impl Pretty<'_> {
+    fn bytes(&self, f: &mut Formatter<'_>, b: &[u8]) -> Result {
+        match std::str::from_utf8(b) {
+            Ok(s) if s.chars().count() > self.max_text => {
+                let s: String = s.chars().take(self.max_text).collect();
+                write!(f, "{s:?}… ({} bytes)", b.len())
+            }
+            Ok(s) => write!(f, "{s:?} ({} bytes)", b.len()),
+            Err(_) if b.len() > self.max_hex => {
+                write!(
+                    f,
+                    "<{} bytes> {}…",
+                    b.len(),
+                    hex::encode(&b[..self.max_hex])
+                )
+            }
+            Err(_) => write!(f, "<{} bytes> {}", b.len(), hex::encode(b)),
+        }
+    }
+
+    fn key(&self, f: &mut Formatter<'_>, k: &[u8]) -> Result {
+        match std::str::from_utf8(k) {
+            Ok(s) => write!(f, "{s}"),
+            Err(_) => write!(f, "<{}>", hex::encode(k)),
+        }
+    }
+
+    fn more(&self, f: &mut Formatter<'_>, len: usize, depth: usize) -> Result {
+        if len > self.max_items {
+            let pad = INDENT.repeat(depth + 1);
+            writeln!(f, "{pad}… {} more", len - self.max_items)?;
+        }
+        Ok(())
+    }
+
+    fn node(&self, f: &mut Formatter<'_>, value: &Ben, depth: usize) -> Result {
+        let pad = INDENT.repeat(depth + 1);
+        match value {
+            Ben::Bytes(b) => self.bytes(f, b),
+            Ben::Number(i) => write!(f, "{i}"),
+            Ben::List(l) if l.is_empty() => write!(f, "[]"),
+            Ben::Map(m) if m.is_empty() => write!(f, "{{}}"),
+            Ben::List(l) => {
+                writeln!(f, "[ ({} items)", l.len())?;
+                for b in l.iter().take(self.max_items) {
+                    write!(f, "{pad}")?;
+                    self.node(f, b, depth + 1)?;
+                    writeln!(f)?;
+                }
+                self.more(f, l.len(), depth)?;
+                write!(f, "{}]", INDENT.repeat(depth))
+            }
+            Ben::Map(m) => {
+                writeln!(f, "{{")?;
+                for (k, v) in m.iter().take(self.max_items) {
+                    write!(f, "{pad}")?;
+                    self.key(f, k)?;
+                    write!(f, ": ")?;
+                    self.node(f, v, depth + 1)?;
+                    writeln!(f)?;
+                }
+                self.more(f, m.len(), depth)?;
+                write!(f, "{}}}", INDENT.repeat(depth))
+            }
+        }
+    }
+}
+
+impl Display for Pretty<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        self.node(f, self.value, 0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_torrent() {
+        let data = std::fs::read("sample.torrent").unwrap();
+        let b = Ben::try_from(data.as_slice()).unwrap();
+        let expected = r#"{
+  announce: "http://bittorrent-test-tracker.codecrafters.io/announce" (55 bytes)
+  created by: "mktorrent 1.1" (13 bytes)
+  info: {
+    length: 92063
+    name: "sample.txt" (10 bytes)
+    piece length: 32768
+    pieces: <60 bytes> e876f67a2a8886e8f36b136726c30fa29703022d…
+  }
+}"#;
+        assert_eq!(b.pretty().to_string(), expected);
+    }
+
+    #[test]
+    fn test_truncation() {
+        let b = Ben::List((0..40).map(Ben::Number).collect());
+        let s = b.pretty().to_string();
+        assert!(s.starts_with("[ (40 items)\n  0\n"));
+        assert!(s.ends_with("  31\n  … 8 more\n]"));
+
+        let b = Ben::Bytes("x".repeat(100).into());
+        assert_eq!(
+            b.pretty().to_string(),
+            format!("{:?}… (100 bytes)", "x".repeat(80))
+        );
+        assert_eq!(Ben::List(vec![]).pretty().to_string(), "[]");
+    }
+}
@@ -0,0 +1,170 @@
+use crate::ben::{
+    path::{Path, Segment},
+    Ben,
+};
+use anyhow::{Context, Result};
+use std::str::FromStr;
+
+/// Path expression selecting nodes of a bencode tree.
+///
+/// Keys are separated by `.`, list elements are selected with `[n]` and every
+/// child of a list or dictionary with `[*]` or `.*`. Keys containing `.`, `[`
+/// or `]` can be quoted: `["created.by"]`. For example `info.files[*].path`
+/// or `announce-list[0][0]`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Query(Vec<Step>);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Step {
+    Key(Vec<u8>),
+    Index(usize),
+    Wildcard,
+}
+
+impl FromStr for Query {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self> {
+        let mut steps = vec![];
+        let mut rest = s.strip_prefix('.').unwrap_or(s);
+        let mut dotted = true;
+        while !rest.is_empty() {
+            if let Some(r) = rest.strip_prefix('[') {
+                let (step, r) = bracket(r).with_context(|| format!("invalid query: {s}"))?;
+                steps.push(step);
+                rest = r;
+                dotted = false;
+            } else if let Some(r) = rest.strip_prefix('.').filter(|_| !dotted) {
+                rest = r;
+                dotted = true;
+            } else if dotted {
+                let end = rest.find(['.', '[', ']']).unwrap_or(rest.len());
+                anyhow::ensure!(end > 0, "invalid query: empty key in {s}");
+                let key = &rest[..end];
+                steps.push(match key {
+                    "*" => Step::Wildcard,
+                    k => Step::Key(k.into()),
+                });
+                rest = &rest[end..];
+                dotted = false;
+            } else {
+                anyhow::bail!("invalid query: unexpected {rest:?} in {s}");
+            }
+        }
+        anyhow::ensure!(
+            !dotted || steps.is_empty(),
+            "invalid query: trailing dot in {s}"
+        );
+        Ok(Self(steps))
+    }
+}
+
+/// Parses the inside of `[...]`, returning the step and what follows `]`.
+fn bracket(input: &str) -> Result<(Step, &str)> {
+    if let Some(r) = input.strip_prefix('"') {
+        let (key, r) = r.split_once('"').context("unterminated quoted key")?;
+        let r = r.strip_prefix(']').context("missing ]")?;
+        return Ok((Step::Key(key.into()), r));
+    }
+    let (inner, r) = input.split_once(']').context("missing ]")?;
+    let step = match inner {
+        "*" => Step::Wildcard,
+        n => Step::Index(n.parse().with_context(|| format!("invalid index {n:?}"))?),
+    };
+    Ok((step, r))
+}
+
+impl Ben {
+    /// Every node matching `q`, along with its path.
+    pub fn query(&self, q: &Query) -> Vec<(Path, &Ben)> {
+        q.0.iter().fold(vec![(Path::root(), self)], |nodes, step| {
+            nodes
+                .into_iter()
+                .flat_map(|(path, node)| {
+                    children(node, step).map(move |(s, b)| (path.clone().child(s), b))
+                })
+                .collect()
+        })
+    }
+}
+
+fn children<'a>(node: &'a Ben, step: &Step) -> Box<dyn Iterator<Item = (Segment, &'a Ben)> + 'a> {
+    match (node, step) {
+        (Ben::Map(m), Step::Key(k)) => Box::new(
+            m.get_key_value(k)
+                .map(|(k, v)| (Segment::Key(k.clone()), v))
+                .into_iter(),
+        ),
+        (Ben::List(l), Step::Index(i)) => {
+            Box::new(l.get(*i).map(|v| (Segment::Index(*i), v)).into_iter())
+        }
+        (Ben::Map(m), Step::Wildcard) => {
+            Box::new(m.iter().map(|(k, v)| (Segment::Key(k.clone()), v)))
+        }
+        (Ben::List(l), Step::Wildcard) => {
+            Box::new(l.iter().enumerate().map(|(i, v)| (Segment::Index(i), v)))
+        }
+        _ => Box::new(std::iter::empty()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn torrent() -> Ben {
+        let json = serde_json::json!({
+            "announce-list": [["http://a"], ["http://b", "udp://c"]],
+            "info": {
+                "files": [
+                    {"length": 1, "path": ["dir", "a.txt"]},
+                    {"length": 2, "path": ["b.txt"]},
+                ],
+                "piece length": 16384,
+                "created.by": "me",
+            }
+        });
+        Ben::try_from(&json).unwrap()
+    }
+
+    fn values(b: &Ben, q: &str) -> Vec<String> {
+        let q: Query = q.parse().unwrap();
+        b.query(&q).iter().map(|m| m.1.to_string()).collect()
+    }
+
+    #[test]
+    fn test_paths() {
+        let t = torrent();
+        assert_eq!(values(&t, "announce-list[1][0]"), vec![r#""http://b""#]);
+        assert_eq!(values(&t, "info.piece length"), vec!["16384"]);
+        assert_eq!(values(&t, r#".info["created.by"]"#), vec![r#""me""#]);
+        assert_eq!(
+            values(&t, "info.files[*].path"),
+            vec![r#"["dir","a.txt"]"#, r#"["b.txt"]"#]
+        );
+        assert_eq!(values(&t, "announce-list[*][1]"), vec![r#""udp://c""#]);
+        assert_eq!(values(&t, "info.files[5]").len(), 0);
+        assert_eq!(values(&t, "").len(), 1);
+    }
+
+    #[test]
+    fn test_match_paths() {
+        let t = torrent();
+        let q: Query = "info.files.*.length".parse().unwrap();
+        let paths: Vec<_> = t.query(&q).iter().map(|m| m.0.to_string()).collect();
+        assert_eq!(paths, vec!["info.files[0].length", "info.files[1].length"]);
+    }
+
+    #[test]
+    fn test_invalid() {
+        for q in [
+            "info..name",
+            "info.",
+            "files[x]",
+            "files[0",
+            r#"["a]"#,
+            "a]",
+        ] {
+            assert!(q.parse::<Query>().is_err(), "{q} should be invalid");
+        }
+    }
+}
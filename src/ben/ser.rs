@@ -0,0 +1,409 @@
+use crate::ben::{
+    error::{Error, ErrorKind},
+    path::Segment,
+    Ben, List, Map,
+};
+use serde::{ser, Serialize};
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Converts `value` into a bencode tree.
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<Ben> {
+    value
+        .serialize(Serializer)?
+        .ok_or_else(|| unsupported("a missing value"))
+}
+
+/// Serializes `value` as canonical bencode.
+pub fn to_bytes<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>> {
+    to_value(value).map(|b| b.to_bytes())
+}
+
+fn unsupported(what: &str) -> Error {
+    ErrorKind::Message(format!("bencode cannot represent {what}")).into()
+}
+
+impl Serialize for Ben {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        use ser::{SerializeMap, SerializeSeq};
+        match self {
+            Ben::Bytes(b) => serializer.serialize_bytes(b),
+            Ben::Number(i) => serializer.serialize_i64(*i),
+            Ben::List(l) => {
+                let mut seq = serializer.serialize_seq(Some(l.len()))?;
+                l.iter().try_for_each(|b| seq.serialize_element(b))?;
+                seq.end()
+            }
+            Ben::Map(m) => {
+                let mut map = serializer.serialize_map(Some(m.len()))?;
+                for (k, v) in m {
+                    map.serialize_entry(serde_bytes::Bytes::new(k), v)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+/// Serializer producing a [`Ben`], or `None` for values bencode omits, such
+/// as `None` fields.
+struct Serializer;
+
+impl ser::Serializer for Serializer {
+    type Ok = Option<Ben>;
+    type Error = Error;
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = Variant<SeqSerializer>;
+    type SerializeMap = MapSerializer;
+    type SerializeStruct = MapSerializer;
+    type SerializeStructVariant = Variant<MapSerializer>;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok> {
+        Ok(Some(Ben::Number(v)))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok> {
+        let v = i64::try_from(v).map_err(|_| Error::from(ErrorKind::IntegerOverflow))?;
+        self.serialize_i64(v)
+    }
+
+    fn serialize_f32(self, _: f32) -> Result<Self::Ok> {
+        Err(unsupported("floating point numbers"))
+    }
+
+    fn serialize_f64(self, _: f64) -> Result<Self::Ok> {
+        Err(unsupported("floating point numbers"))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok> {
+        self.serialize_bytes(v.as_bytes())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok> {
+        Ok(Some(Ben::Bytes(v.to_vec())))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok> {
+        Ok(None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok> {
+        Ok(None)
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<Self::Ok> {
+        Ok(None)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok> {
+        let value = to_value(value).map_err(|e| e.within(Segment::Key(variant.into())))?;
+        Ok(Some(Ben::Map(Map::from([(variant.into(), value)]))))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Ok(SeqSerializer(List::with_capacity(len.unwrap_or_default())))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        let inner = self.serialize_seq(Some(len))?;
+        Ok(Variant { variant, inner })
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap> {
+        Ok(MapSerializer::default())
+    }
+
+    fn serialize_struct(self, _: &'static str, len: usize) -> Result<Self::SerializeStruct> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        let inner = self.serialize_map(Some(len))?;
+        Ok(Variant { variant, inner })
+    }
+}
+
+struct SeqSerializer(List);
+
+impl SeqSerializer {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let value = to_value(value).map_err(|e| e.within(Segment::Index(self.0.len())))?;
+        self.0.push(value);
+        Ok(())
+    }
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = Option<Ben>;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        Ok(Some(Ben::List(self.0)))
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+    type Ok = Option<Ben>;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+    type Ok = Option<Ben>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+#[derive(Default)]
+struct MapSerializer {
+    map: Map,
+    key: Option<Vec<u8>>,
+}
+
+impl MapSerializer {
+    fn insert<T: Serialize + ?Sized>(&mut self, key: Vec<u8>, value: &T) -> Result<()> {
+        let value = value
+            .serialize(Serializer)
+            .map_err(|e| e.within(Segment::Key(key.clone())))?;
+        if let Some(value) = value {
+            self.map.insert(key, value);
+        }
+        Ok(())
+    }
+}
+
+impl ser::SerializeMap for MapSerializer {
+    type Ok = Option<Ben>;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        match to_value(key)? {
+            Ben::Bytes(b) => self.key = Some(b),
+            Ben::Number(i) => self.key = Some(i.to_string().into_bytes()),
+            _ => return Err(unsupported("dictionary keys that are not strings")),
+        }
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| unsupported("a value without key"))?;
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        Ok(Some(Ben::Map(self.map)))
+    }
+}
+
+impl ser::SerializeStruct for MapSerializer {
+    type Ok = Option<Ben>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.insert(key.into(), value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        ser::SerializeMap::end(self)
+    }
+}
+
+/// Enum variant with data, encoded as a dictionary with the variant name as its only key.
+struct Variant<S> {
+    variant: &'static str,
+    inner: S,
+}
+
+impl<S> Variant<S> {
+    fn wrap(variant: &'static str, value: Option<Ben>) -> Result<Option<Ben>> {
+        let value = value.ok_or_else(|| unsupported("an empty variant"))?;
+        Ok(Some(Ben::Map(Map::from([(variant.into(), value)]))))
+    }
+}
+
+impl ser::SerializeTupleVariant for Variant<SeqSerializer> {
+    type Ok = Option<Ben>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.inner.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        Self::wrap(self.variant, ser::SerializeSeq::end(self.inner)?)
+    }
+}
+
+impl ser::SerializeStructVariant for Variant<MapSerializer> {
+    type Ok = Option<Ben>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.inner.insert(key.into(), value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        Self::wrap(self.variant, ser::SerializeMap::end(self.inner)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde::Serialize;
+
+    #[derive(Serialize)]
+    struct Info {
+        name: String,
+        #[serde(rename = "piece length")]
+        piece_length: u32,
+        private: Option<bool>,
+        #[serde(with = "serde_bytes")]
+        pieces: Vec<u8>,
+        files: Vec<(u64, Vec<&'static str>)>,
+    }
+
+    #[test]
+    fn test_struct() {
+        let info = Info {
+            name: "hernan".into(),
+            piece_length: 16,
+            private: None,
+            pieces: vec![0xff, 0x00],
+            files: vec![(2, vec!["a", "b"])],
+        };
+        let bytes = to_bytes(&info).unwrap();
+        assert_eq!(
+            bytes,
+            b"d5:fileslli2el1:a1:beee4:name6:hernan12:piece lengthi16e6:pieces2:\xff\x00e"
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let e = to_value(&vec![(1, 0.5)]).unwrap_err();
+        assert_eq!(e.path.to_string(), "[0][1]");
+        assert!(to_value(&u64::MAX).is_err());
+        assert!(to_value(&()).is_err());
+    }
+
+    #[test]
+    fn test_ben_round_trip() {
+        let b: Ben = "d1:ali1e2:xye1:bi-3ee".parse().unwrap();
+        assert_eq!(to_value(&b).unwrap(), b);
+    }
+}
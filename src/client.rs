@@ -1,16 +1,27 @@
 mod connect;
 mod download;
+mod extension;
+mod layout;
 mod message;
+mod metadata;
 mod peer;
+mod state;
 mod stream;
-use crate::{hash::Hash, torrent::Torrent};
-use anyhow::Result;
+#[cfg(test)]
+mod testing;
+use crate::{hash::Hash, magnet::Magnet, torrent::Torrent};
+use anyhow::{Context, Result};
+use extension::Registry;
 pub use peer::Peer;
+use reqwest::Url;
 use serde::Serialize;
-use std::path::Path;
+use std::{fmt::Display, path::PathBuf, str::FromStr, time::Duration};
 pub use stream::Stream;
 
 const CLIENT_ID: &[u8; 20] = b"bittorrent-hernan-rs";
+/// Time given to a peer to complete an exchange, such as the extension
+/// handshake or sending the whole metadata.
+pub const PEER_TIMEOUT: Duration = Duration::from_secs(30);
 
 #[repr(u8)]
 #[derive(Debug, Serialize)]
@@ -18,24 +29,74 @@ enum Compact {
     Enabled = 1,
 }
 
+/// Torrent to work on, given as a metainfo file or a magnet link.
+#[derive(Clone, Debug)]
+pub enum Source {
+    File(PathBuf),
+    Magnet(Magnet),
+}
+
+impl FromStr for Source {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self> {
+        match s.starts_with("magnet:") {
+            true => Ok(Self::Magnet(s.parse()?)),
+            false => Ok(Self::File(s.into())),
+        }
+    }
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Source::File(p) => p.display().fmt(f),
+            Source::Magnet(m) => match &m.name {
+                Some(name) => name.fmt(f),
+                None => m.info_hash.fmt(f),
+            },
+        }
+    }
+}
+
 pub struct Client {
     id: Hash,
-    torrent: Torrent,
+    info_hash: Hash,
+    trackers: Vec<Url>,
+    /// Metainfo, unknown until fetched from peers when opened from a magnet.
+    torrent: Option<Torrent>,
     peers: Vec<Peer>,
+    registry: Registry,
 }
 
 impl Client {
-    fn new(torrent: Torrent) -> Self {
+    pub fn open(source: &Source) -> Result<Self> {
         let id = Hash::new(*CLIENT_ID);
-        Self {
-            id,
-            torrent,
-            peers: vec![],
+        match source {
+            Source::File(p) => {
+                let t = Torrent::open(p)?;
+                Ok(Self {
+                    id,
+                    info_hash: t.info_hash()?,
+                    trackers: t.trackers(),
+                    torrent: Some(t),
+                    peers: vec![],
+                    registry: Registry::default(),
+                })
+            }
+            Source::Magnet(m) => Ok(Self {
+                id,
+                info_hash: m.info_hash,
+                trackers: m.trackers.clone(),
+                torrent: None,
+                peers: m.peers.clone(),
+                registry: Registry::default(),
+            }),
         }
     }
 
-    pub fn open(p: &Path) -> Result<Self> {
-        let t = Torrent::open(p)?;
-        Ok(Self::new(t))
+    fn torrent(&self) -> Result<&Torrent> {
+        self.torrent
+            .as_ref()
+            .context("Torrent metadata is not available")
     }
 }
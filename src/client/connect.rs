@@ -1,12 +1,22 @@
 use super::{Client, Compact, Peer, Stream};
-use crate::{hash::Hash, torrent::Torrent};
-use anyhow::{Ok, Result};
+use crate::{
+    ben::{self, Incremental, Options},
+    hash::Hash,
+};
+use anyhow::{Context, Result};
+use reqwest::Url;
 use serde::{Deserialize, Serialize};
 
 impl Client {
     pub async fn discover_peers(&mut self) -> Result<&Vec<Peer>> {
         if self.peers.is_empty() {
-            self.peers = discover(self.id, &self.torrent).await?;
+            // Unknown until the metadata is fetched, but must not read as a seeder.
+            let left = match &self.torrent {
+                Some(t) => t.info.length()?,
+                None => 1,
+            };
+            let req = Request::new(self.id, self.info_hash, left);
+            self.peers = discover(&self.trackers, &req).await?;
         }
         Ok(&self.peers)
     }
@@ -16,14 +26,34 @@ impl Client {
     }
 }
 
-async fn discover(id: Hash, t: &Torrent) -> Result<Vec<Peer>> {
-    let mut url = t.announce.clone();
-    let req = Request::new(id, t)?;
+/// Peers from the first tracker that answers.
+async fn discover(trackers: &[Url], req: &Request) -> Result<Vec<Peer>> {
+    let mut error = anyhow::anyhow!("No trackers available");
+    for url in trackers {
+        match announce(url.clone(), req).await {
+            Ok(peers) => return Ok(peers),
+            Err(e) => error = e.context(format!("Tracker {url}")),
+        }
+    }
+    Err(error)
+}
+
+async fn announce(mut url: Url, req: &Request) -> Result<Vec<Peer>> {
     let q = req.url_encoded()?;
     url.set_query(Some(&q));
-    let res = reqwest::get(url).await?;
-    let body = res.bytes().await?;
-    let res: Response = serde_bencode::from_bytes(&body)?;
+    let mut res = reqwest::get(url).await?;
+    let mut body = Incremental::new(Options::untrusted());
+    let res = loop {
+        if let Some(ben) = body.next_value()? {
+            break ben;
+        }
+        let chunk = res.chunk().await?.context("Truncated tracker response")?;
+        body.feed(chunk);
+    };
+    let res: Response = ben::from_value(&res)?;
+    if let Some(reason) = res.failure_reason {
+        anyhow::bail!("Tracker failure: {reason}");
+    }
     res.peers
         .chunks(std::mem::size_of::<Peer>())
         .map(Peer::try_from)
@@ -37,23 +67,23 @@ struct Request {
     #[serde(with = "hash")]
     peer_id: Hash,
     port: u16,
-    uploaded: u32,
-    downloaded: u32,
-    left: u32,
+    uploaded: u64,
+    downloaded: u64,
+    left: u64,
     compact: u8,
 }
 
 impl Request {
-    fn new(id: Hash, t: &Torrent) -> Result<Self> {
-        Ok(Self {
-            info_hash: t.info.hash()?,
+    fn new(id: Hash, info_hash: Hash, left: u64) -> Self {
+        Self {
+            info_hash,
             peer_id: id,
             port: u16::MAX,
             uploaded: 0,
             downloaded: 0,
-            left: t.info.length,
+            left,
             compact: Compact::Enabled as u8,
-        })
+        }
     }
 
     fn url_encoded(&self) -> Result<String> {
@@ -65,7 +95,9 @@ impl Request {
 
 #[derive(Debug, Deserialize)]
 struct Response {
-    #[serde(with = "serde_bytes")]
+    #[serde(rename = "failure reason")]
+    failure_reason: Option<String>,
+    #[serde(default, with = "serde_bytes")]
     peers: Vec<u8>,
 }
 
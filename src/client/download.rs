@@ -1,28 +1,33 @@
 use super::{
-    message::{
-        payload::{Piece, Request},
-        Code, Outgoing,
-    },
+    layout::Layout,
+    message::{payload::Request, Message},
     Client, Stream,
 };
 use crate::{hash::Hash, torrent::Torrent};
 use anyhow::{ensure, Context, Ok, Result};
 use bytes::{BufMut, Bytes, BytesMut};
-use std::{cmp::min, fs::File, io::Read, path::Path};
-use std::{io::Write, path::PathBuf};
+use std::{
+    cmp::min,
+    collections::VecDeque,
+    fs::File,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
 
 pub const CHUNK_SIZE: u32 = 16 * 1024;
+/// Block requests kept in flight on a connection.
+const MAX_OUTSTANDING: usize = 5;
 
 struct Fetch {
     hash: Hash,
     parts: Vec<Request>,
 }
 
-impl<'a> Torrent {
+impl Torrent {
     fn fetch_index(&self, index: u32) -> Result<Fetch> {
         let hash = self.info.piece_at(index as usize)?;
-        let info = &self.info;
-        let mut remains = min(info.length - info.piece_length * index, info.piece_length);
+        let length = self.info.piece_length_at(index as usize)?;
+        let mut remains = u32::try_from(length).context("Piece too large")?;
         let mut parts = Vec::with_capacity(self.info.piece_count());
         while remains > 0 {
             let len = min(remains, CHUNK_SIZE);
@@ -33,28 +38,68 @@ impl<'a> Torrent {
         Ok(Fetch { hash, parts })
     }
 
-    fn fetch_all(&'a self) -> impl Iterator<Item = Fetch> + 'a {
-        (0..self.info.piece_count() as u32).flat_map(|i| self.fetch_index(i))
+    fn fetch_all(&self) -> Result<Vec<Fetch>> {
+        let count = u32::try_from(self.info.piece_count()).context("Too many pieces")?;
+        (0..count).map(|i| self.fetch_index(i)).collect()
     }
 }
 
 impl Stream {
-    async fn fetch(&mut self, req: Request) -> Result<Piece> {
-        let out = Outgoing::request(req);
-        self.write_message(&out).await?;
-        let inc = self.read().await?;
-        inc.payload()
-    }
-
+    /// Downloads a piece, pipelining block requests while the peer allows it.
+    /// Requests dropped by a choke are sent again once unchoked.
     async fn download(&mut self, fetch: Fetch) -> Result<Bytes> {
-        self.read_code(Code::Bitfield).await?;
-        self.write_code(Code::Interested).await?;
-        self.read_code(Code::Unchoke).await?;
+        let index = fetch.parts.first().context("Empty piece")?.index;
+        let mut blocks: Vec<Option<Bytes>> = vec![None; fetch.parts.len()];
+        let mut pending: VecDeque<usize> = (0..fetch.parts.len()).collect();
+        let mut outstanding: Vec<usize> = vec![];
+
+        if !self.state.am_interested {
+            self.write(&Message::Interested).await?;
+        }
+        while blocks.iter().any(Option::is_none) {
+            if !self.state.peer_choking {
+                ensure!(
+                    self.state.bitfield.has(index),
+                    "Peer does not have piece {index}"
+                );
+                while outstanding.len() < MAX_OUTSTANDING {
+                    let Some(i) = pending.pop_front() else {
+                        break;
+                    };
+                    self.write(&Message::Request(fetch.parts[i])).await?;
+                    outstanding.push(i);
+                }
+            }
+
+            match self.read().await? {
+                Message::Piece(piece) if piece.index == index => {
+                    let i = (piece.begin / CHUNK_SIZE) as usize;
+                    let Some(part) = fetch.parts.get(i) else {
+                        continue;
+                    };
+                    ensure!(
+                        part.begin == piece.begin && part.length as usize == piece.data.len(),
+                        "Peer sent an invalid block {}:{}",
+                        piece.index,
+                        piece.begin
+                    );
+                    // Blocks requested before a choke may still arrive.
+                    outstanding.retain(|o| *o != i);
+                    pending.retain(|p| *p != i);
+                    blocks[i] = Some(piece.data);
+                }
+                Message::Choke => {
+                    for i in outstanding.drain(..).rev() {
+                        pending.push_front(i);
+                    }
+                }
+                _ => {}
+            }
+        }
 
         let mut buffer = BytesMut::new();
-        for r in fetch.parts {
-            let piece = self.fetch(r).await?;
-            buffer.put(piece.data);
+        for block in blocks.into_iter().flatten() {
+            buffer.put(block);
         }
 
         let hash = Hash::encode(&buffer)?;
@@ -77,7 +122,7 @@ impl Client {
     }
 
     pub async fn download_piece(&mut self, index: u32, out: &Path) -> Result<()> {
-        let req = self.torrent.fetch_index(index)?;
+        let req = self.metadata().await?.fetch_index(index)?;
         let mut conn = self.deque_stream().await?;
         let chunk = conn.download(req).await?;
         let mut file = File::create(out)?;
@@ -86,20 +131,21 @@ impl Client {
     }
 
     fn collect_parts(&self, paths: &[PathBuf], out: &Path) -> Result<()> {
-        let mut out = File::create(out)?;
+        let info = &self.torrent()?.info;
+        let mut out = Layout::create(out, info)?;
         for path in paths {
             let mut f = File::open(path)?;
-            let mut buffer = Vec::with_capacity(self.torrent.info.piece_length as usize);
+            let mut buffer = Vec::with_capacity(info.piece_length as usize);
             f.read_to_end(&mut buffer)?;
             out.write_all(&buffer)?;
             drop(f);
             std::fs::remove_file(path)?;
         }
-        Ok(())
+        out.finish()
     }
 
     pub async fn download(&mut self, out: &Path) -> Result<()> {
-        let pieces: Vec<_> = self.torrent.fetch_all().collect();
+        let pieces = self.metadata().await?.fetch_all()?;
         let mut parts = vec![];
         for p in pieces {
             let out = out.with_extension(p.hash.digest());
@@ -112,3 +158,84 @@ impl Client {
         self.collect_parts(&parts, out)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{
+        message::payload::Piece,
+        testing::{accept, listen, receive, send},
+        Source,
+    };
+    use tokio::{
+        io::AsyncReadExt,
+        net::{TcpListener, TcpStream},
+    };
+
+    async fn block(s: &mut TcpStream, data: &[u8]) -> u32 {
+        let Message::Request(r) = receive(s).await else {
+            panic!("Expected a request");
+        };
+        let begin = r.begin as usize;
+        let piece = Piece {
+            index: r.index,
+            begin: r.begin,
+            data: Bytes::copy_from_slice(&data[begin..begin + r.length as usize]),
+        };
+        send(s, Message::Piece(piece)).await;
+        r.begin / CHUNK_SIZE
+    }
+
+    /// Peer announcing the piece with `Have`, then choking mid-piece.
+    async fn serve(listener: TcpListener, data: Vec<u8>) {
+        let mut s = accept(listener).await;
+
+        send(&mut s, Message::KeepAlive).await;
+        send(&mut s, Message::Have(0)).await;
+        assert_eq!(receive(&mut s).await, Message::Interested);
+        send(&mut s, Message::Unchoke).await;
+
+        // Answer one of the pipelined requests, dropping the others.
+        let first = block(&mut s, &data).await;
+        for _ in 1..MAX_OUTSTANDING {
+            assert!(matches!(receive(&mut s).await, Message::Request(_)));
+        }
+        send(&mut s, Message::Choke).await;
+        send(&mut s, Message::Unchoke).await;
+        // Requests sent before the choke was seen may come along, either way
+        // every block is served again.
+        let mut served = vec![first];
+        while served.len() < data.len() / CHUNK_SIZE as usize {
+            let i = block(&mut s, &data).await;
+            if !served.contains(&i) {
+                served.push(i);
+            }
+        }
+        while s.read_u8().await.is_ok() {}
+    }
+
+    #[tokio::test]
+    async fn test_download_choked_mid_piece() {
+        let data: Vec<u8> = (0..8 * CHUNK_SIZE).map(|i| i as u8).collect();
+        let parts = (0..8)
+            .map(|i| Request::new(0, i * CHUNK_SIZE, CHUNK_SIZE))
+            .collect();
+        let hash = Hash::encode(&data).unwrap();
+        let fetch = Fetch { hash, parts };
+
+        let (listener, port) = listen().await;
+        let server = tokio::spawn(serve(listener, data.clone()));
+
+        let link = format!(
+            "magnet:?xt=urn:btih:{}&x.pe=127.0.0.1:{port}",
+            hash.digest()
+        );
+        let mut client = Client::open(&link.parse::<Source>().unwrap()).unwrap();
+        let peer = client.discover_peers().await.unwrap()[0];
+        let mut conn = client.connect(peer).await.unwrap();
+        assert_eq!(conn.download(fetch).await.unwrap(), data);
+        assert!(!conn.state.peer_choking && conn.state.am_interested);
+        drop(conn);
+        server.await.unwrap();
+    }
+}
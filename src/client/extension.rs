@@ -0,0 +1,240 @@
+use super::{
+    message::{payload::Extended, Message},
+    Stream,
+};
+use crate::ben::{self, Ben, Options};
+use anyhow::{ensure, Context, Result};
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, net::IpAddr};
+
+/// Id of the extension handshake message.
+const HANDSHAKE_ID: u8 = 0;
+/// Messages of other extensions kept while reading one, older ones being
+/// dropped past it.
+const MAX_QUEUED: usize = 64;
+const CLIENT_NAME: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+/// Extensions this client handles, with the id peers must use when sending
+/// us their messages. Messages of a registered extension are queued on the
+/// stream until read with [`Stream::read_extension`].
+#[derive(Clone, Debug)]
+pub struct Registry(BTreeMap<String, u8>);
+
+impl Default for Registry {
+    fn default() -> Self {
+        let mut r = Self(BTreeMap::new());
+        r.register("ut_metadata");
+        r
+    }
+}
+
+impl Registry {
+    /// Adds an extension, returning its local message id.
+    pub fn register(&mut self, name: &str) -> u8 {
+        let next = self.0.values().max().map_or(1, |id| id + 1);
+        *self.0.entry(name.into()).or_insert(next)
+    }
+
+    fn id(&self, name: &str) -> Option<u8> {
+        self.0.get(name).copied()
+    }
+
+    fn contains(&self, id: u8) -> bool {
+        self.0.values().any(|v| *v == id)
+    }
+}
+
+/// Extension handshake (BEP 10), as sent by either side.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ExtensionHandshake {
+    /// Extension names to the message id the sender expects for them, 0
+    /// meaning disabled.
+    #[serde(default)]
+    pub m: BTreeMap<String, i64>,
+    /// Client name and version.
+    pub v: Option<String>,
+    /// Listening port.
+    pub p: Option<u16>,
+    /// Outstanding requests the sender accepts.
+    pub reqq: Option<u32>,
+    /// Address of the receiver, as seen by the sender.
+    #[serde(default, with = "serde_bytes")]
+    pub yourip: Option<Vec<u8>>,
+    /// Size of the `info` dictionary (BEP 9).
+    pub metadata_size: Option<usize>,
+}
+
+impl ExtensionHandshake {
+    /// Message id to use when sending messages of extension `name`.
+    pub fn id(&self, name: &str) -> Option<u8> {
+        self.m
+            .get(name)
+            .and_then(|id| u8::try_from(*id).ok())
+            .filter(|id| *id != HANDSHAKE_ID)
+    }
+
+    pub fn yourip(&self) -> Option<IpAddr> {
+        let ip = self.yourip.as_deref()?;
+        match ip.len() {
+            4 => Some(IpAddr::from(<[u8; 4]>::try_from(ip).ok()?)),
+            16 => Some(IpAddr::from(<[u8; 16]>::try_from(ip).ok()?)),
+            _ => None,
+        }
+    }
+}
+
+impl Stream {
+    /// Exchanges extension handshakes, once, returning the remote one.
+    pub async fn extension_handshake(&mut self) -> Result<&ExtensionHandshake> {
+        ensure!(self.extensions, "Peer does not support extensions");
+        if self.remote.is_none() {
+            let ours = ExtensionHandshake {
+                m: self
+                    .registry
+                    .0
+                    .iter()
+                    .map(|(k, v)| (k.clone(), *v as i64))
+                    .collect(),
+                v: Some(CLIENT_NAME.into()),
+                yourip: self.stream.peer_addr().ok().map(|a| match a.ip() {
+                    IpAddr::V4(ip) => ip.octets().to_vec(),
+                    IpAddr::V6(ip) => ip.octets().to_vec(),
+                }),
+                ..Default::default()
+            };
+            self.write_extended(HANDSHAKE_ID, ben::to_bytes(&ours)?.into())
+                .await?;
+            while self.remote.is_none() {
+                self.read_extended().await?;
+            }
+        }
+        self.remote.as_ref().context("No extension handshake")
+    }
+
+    /// Sends a message of extension `name`, as negotiated with the peer.
+    pub async fn write_extension(&mut self, name: &str, payload: Vec<u8>) -> Result<()> {
+        let id = self
+            .extension_handshake()
+            .await?
+            .id(name)
+            .with_context(|| format!("Peer does not support {name}"))?;
+        self.write_extended(id, payload.into()).await
+    }
+
+    /// Next message of extension `name`, queueing the ones of other
+    /// registered extensions and skipping any other message.
+    pub async fn read_extension(&mut self, name: &str) -> Result<Bytes> {
+        let id = self
+            .registry
+            .id(name)
+            .with_context(|| format!("Extension not registered: {name}"))?;
+        let queued = self.queued.iter().position(|e| e.id == id);
+        if let Some(ext) = queued.and_then(|i| self.queued.remove(i)) {
+            return Ok(ext.payload);
+        }
+        loop {
+            let ext = self.read_extended().await?;
+            if ext.id == id {
+                return Ok(ext.payload);
+            }
+            if ext.id != HANDSHAKE_ID && self.registry.contains(ext.id) {
+                if self.queued.len() == MAX_QUEUED {
+                    self.queued.pop_front();
+                }
+                self.queued.push_back(ext);
+            }
+        }
+    }
+
+    async fn write_extended(&mut self, id: u8, payload: Bytes) -> Result<()> {
+        self.write(&Message::Extended(Extended { id, payload }))
+            .await
+    }
+
+    /// Next extension message, keeping track of handshakes, which peers may
+    /// send again at any time to update them.
+    async fn read_extended(&mut self) -> Result<Extended> {
+        loop {
+            let Message::Extended(ext) = self.read().await? else {
+                continue;
+            };
+            if ext.id == HANDSHAKE_ID {
+                let ben = Ben::decode_with(&ext.payload, Options::untrusted())?.value;
+                self.remote = Some(ben::from_value(&ben)?);
+            }
+            return Ok(ext);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{
+        testing::{accept, listen, receive, send},
+        Client, Source,
+    };
+
+    #[test]
+    fn test_registry() {
+        let mut r = Registry::default();
+        assert_eq!(r.id("ut_metadata"), Some(1));
+        assert_eq!(r.register("ut_pex"), 2);
+        assert_eq!(r.register("ut_metadata"), 1);
+        assert_eq!(r.id("lt_donthave"), None);
+    }
+
+    #[test]
+    fn test_handshake() {
+        let hs: ExtensionHandshake = ben::from_value(
+            &"d1:md11:ut_metadatai3e6:ut_pexi0ee1:pi6881e4:reqqi250e1:v5:x 1.06:yourip4:\x7f\x00\x00\x01e"
+                .parse::<Ben>()
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(hs.id("ut_metadata"), Some(3));
+        assert_eq!(hs.id("ut_pex"), None);
+        assert_eq!(hs.p, Some(6881));
+        assert_eq!(hs.reqq, Some(250));
+        assert_eq!(hs.v.as_deref(), Some("x 1.0"));
+        assert_eq!(hs.yourip(), Some(IpAddr::from([127, 0, 0, 1])));
+    }
+
+    #[tokio::test]
+    async fn test_queue_other_extensions() {
+        let (listener, port) = listen().await;
+        let server = tokio::spawn(async move {
+            let mut s = accept(listener).await;
+            let mut remote = ExtensionHandshake::default();
+            remote.m.insert("ut_metadata".into(), 3);
+            let payload = ben::to_bytes(&remote).unwrap().into();
+            for (id, payload) in [
+                (HANDSHAKE_ID, payload),
+                (2, Bytes::from_static(b"pex")),
+                (7, Bytes::from_static(b"unknown")),
+                (1, Bytes::from_static(b"metadata")),
+            ] {
+                send(&mut s, Message::Extended(Extended { id, payload })).await;
+            }
+            assert!(matches!(receive(&mut s).await, Message::Extended(_)));
+        });
+
+        let link = format!(
+            "magnet:?xt=urn:btih:{}&x.pe=127.0.0.1:{port}",
+            "ab".repeat(20)
+        );
+        let client = Client::open(&link.parse::<Source>().unwrap()).unwrap();
+        let peer = client.peers[0];
+        let mut stream = client.connect(peer).await.unwrap();
+        assert_eq!(stream.registry.register("ut_pex"), 2);
+        stream.extension_handshake().await.unwrap();
+        assert_eq!(
+            stream.read_extension("ut_metadata").await.unwrap(),
+            "metadata"
+        );
+        assert_eq!(stream.read_extension("ut_pex").await.unwrap(), "pex");
+        assert!(stream.queued.is_empty());
+        server.await.unwrap();
+    }
+}
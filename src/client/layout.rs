@@ -0,0 +1,133 @@
+use crate::torrent::Info;
+use anyhow::Result;
+use std::{
+    cmp::min,
+    collections::VecDeque,
+    fs::File,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+/// Writes the content of a torrent as it comes, split across its files.
+///
+/// Single-file content is written to `root` itself, while multi-file content
+/// is laid out as a directory tree under `root`.
+pub struct Layout {
+    files: VecDeque<(PathBuf, u64)>,
+    current: Option<(File, u64)>,
+}
+
+impl Layout {
+    pub fn create(root: &Path, info: &Info) -> Result<Self> {
+        Ok(Self {
+            files: info.locate(root)?.into(),
+            current: None,
+        })
+    }
+
+    fn open_next(&mut self) -> io::Result<bool> {
+        let Some((path, length)) = self.files.pop_front() else {
+            return Ok(false);
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        self.current = Some((File::create(path)?, length));
+        Ok(true)
+    }
+
+    /// Creates the remaining, empty, files, failing if any content is missing.
+    pub fn finish(mut self) -> Result<()> {
+        self.flush()?;
+        let current = self.current.as_ref().map_or(0, |c| c.1);
+        let missing = current + self.files.iter().map(|f| f.1).sum::<u64>();
+        anyhow::ensure!(missing == 0, "Content is missing {missing} bytes");
+        while self.open_next()? {}
+        anyhow::Ok(())
+    }
+}
+
+impl Write for Layout {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        loop {
+            match &mut self.current {
+                Some((file, remains)) if *remains > 0 => {
+                    let len = min(*remains, buf.len() as u64) as usize;
+                    let len = file.write(&buf[..len])?;
+                    *remains -= len as u64;
+                    return Ok(len);
+                }
+                _ if buf.is_empty() => return Ok(0),
+                _ => {
+                    if !self.open_next()? {
+                        return Err(io::Error::other("More data than torrent files"));
+                    }
+                }
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.current {
+            Some((file, _)) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ben, ben::Ben};
+
+    #[test]
+    fn test_multi_file_tree() {
+        let json = serde_json::json!({
+            "files": [
+                {"length": 3, "path": ["dir", "a.txt"]},
+                {"length": 0, "path": ["empty"]},
+                {"length": 4, "path": ["b.txt"]},
+            ],
+            "name": "root", "piece length": 4, "pieces": "",
+        });
+        let info: Info = ben::from_value(&Ben::try_from(&json).unwrap()).unwrap();
+        let root = tempfile::tempdir().unwrap();
+        let mut out = Layout::create(root.path(), &info).unwrap();
+        out.write_all(b"aaab").unwrap();
+        out.write_all(b"bb").unwrap();
+        out.write_all(b"b").unwrap();
+        assert!(out.write_all(b"c").is_err());
+        out.finish().unwrap();
+
+        let read = |p: &str| std::fs::read_to_string(root.path().join(p)).unwrap();
+        assert_eq!(read("dir/a.txt"), "aaa");
+        assert_eq!(read("empty"), "");
+        assert_eq!(read("b.txt"), "bbbb");
+    }
+
+    #[test]
+    fn test_missing_content() {
+        let json = serde_json::json!({
+            "files": [
+                {"length": 3, "path": ["a.txt"]},
+                {"length": 4, "path": ["b.txt"]},
+            ],
+            "name": "root", "piece length": 4, "pieces": "",
+        });
+        let info: Info = ben::from_value(&Ben::try_from(&json).unwrap()).unwrap();
+        let root = tempfile::tempdir().unwrap();
+        let mut out = Layout::create(root.path(), &info).unwrap();
+        out.write_all(b"aa").unwrap();
+        assert!(out.finish().is_err());
+    }
+
+    #[test]
+    fn test_unsafe_path() {
+        let json = serde_json::json!({
+            "files": [{"length": 3, "path": ["..", "a.txt"]}],
+            "name": "root", "piece length": 4, "pieces": "",
+        });
+        let info: Info = ben::from_value(&Ben::try_from(&json).unwrap()).unwrap();
+        assert!(Layout::create(Path::new("/tmp"), &info).is_err());
+    }
+}
@@ -1,16 +1,25 @@
 use super::stream::{Decodable, Encodable};
 use anyhow::Result;
 use bytes::{Buf, BufMut, Bytes, BytesMut};
+use payload::{Extended, Piece, Request};
 use std::fmt::Display;
 
 #[repr(u8)]
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Code {
+    Choke = 0,
+    Unchoke = 1,
     Interested = 2,
+    NotInterested = 3,
+    Have = 4,
     Bitfield = 5,
-    Unchoke = 1,
     Request = 6,
     Piece = 7,
+    Cancel = 8,
+    /// DHT listening port (BEP 5).
+    Port = 9,
+    /// Extension protocol message (BEP 10).
+    Extended = 20,
 }
 
 impl Display for Code {
@@ -23,11 +32,17 @@ impl Decodable for Code {
     fn decode(bytes: &mut Bytes) -> Result<Self> {
         anyhow::ensure!(bytes.len() >= std::mem::size_of::<Self>());
         match bytes.get_u8() {
-            c if c == Self::Bitfield as u8 => Ok(Self::Bitfield),
-            c if c == Self::Interested as u8 => Ok(Self::Interested),
-            c if c == Self::Unchoke as u8 => Ok(Self::Unchoke),
-            c if c == Self::Request as u8 => Ok(Self::Request),
-            c if c == Self::Piece as u8 => Ok(Self::Piece),
+            0 => Ok(Self::Choke),
+            1 => Ok(Self::Unchoke),
+            2 => Ok(Self::Interested),
+            3 => Ok(Self::NotInterested),
+            4 => Ok(Self::Have),
+            5 => Ok(Self::Bitfield),
+            6 => Ok(Self::Request),
+            7 => Ok(Self::Piece),
+            8 => Ok(Self::Cancel),
+            9 => Ok(Self::Port),
+            20 => Ok(Self::Extended),
             other => anyhow::bail!("Not a valid code: {other}"),
         }
     }
@@ -39,55 +54,105 @@ impl Encodable for Code {
     }
 }
 
-#[derive(Debug)]
-pub struct Incoming {
-    pub code: Code,
-    data: Bytes,
-}
-
-impl Decodable for Incoming {
-    fn decode(bytes: &mut Bytes) -> Result<Self> {
-        let code = Code::decode(bytes)?;
-        let data = bytes.slice(..bytes.remaining());
-        Ok(Self { code, data })
-    }
+/// Peer wire message, without its length prefix.
+#[derive(Debug, PartialEq)]
+pub enum Message {
+    /// Empty message keeping the connection open.
+    KeepAlive,
+    Choke,
+    Unchoke,
+    Interested,
+    NotInterested,
+    /// Index of a piece the peer now has.
+    Have(u32),
+    /// Pieces the peer has, high bit of the first byte being piece 0.
+    Bitfield(Bytes),
+    Request(Request),
+    Piece(Piece),
+    Cancel(Request),
+    Port(u16),
+    Extended(Extended),
 }
 
-impl Incoming {
-    pub fn payload<T: Decodable>(&self) -> Result<T> {
-        T::decode(&mut self.data.clone())
+impl Message {
+    /// Code of the message, `None` for a keep-alive.
+    pub fn code(&self) -> Option<Code> {
+        Some(match self {
+            Message::KeepAlive => return None,
+            Message::Choke => Code::Choke,
+            Message::Unchoke => Code::Unchoke,
+            Message::Interested => Code::Interested,
+            Message::NotInterested => Code::NotInterested,
+            Message::Have(_) => Code::Have,
+            Message::Bitfield(_) => Code::Bitfield,
+            Message::Request(_) => Code::Request,
+            Message::Piece(_) => Code::Piece,
+            Message::Cancel(_) => Code::Cancel,
+            Message::Port(_) => Code::Port,
+            Message::Extended(_) => Code::Extended,
+        })
     }
 }
 
-#[derive(Debug)]
-pub struct Outgoing<Payload> {
-    code: Code,
-    data: Payload,
-}
-
-impl Outgoing<payload::Request> {
-    pub fn request(data: payload::Request) -> Self {
-        Self {
-            code: Code::Request,
-            data,
+impl Decodable for Message {
+    fn decode(bytes: &mut Bytes) -> Result<Self> {
+        if bytes.is_empty() {
+            return Ok(Self::KeepAlive);
         }
+        let code = Code::decode(bytes)?;
+        let fixed = |bytes: &Bytes, len: usize| {
+            anyhow::ensure!(
+                bytes.len() == len,
+                "Message {code} has {} bytes of payload instead of {len}",
+                bytes.len()
+            );
+            Ok(())
+        };
+        let msg = match code {
+            Code::Choke => fixed(bytes, 0).map(|_| Self::Choke)?,
+            Code::Unchoke => fixed(bytes, 0).map(|_| Self::Unchoke)?,
+            Code::Interested => fixed(bytes, 0).map(|_| Self::Interested)?,
+            Code::NotInterested => fixed(bytes, 0).map(|_| Self::NotInterested)?,
+            Code::Have => {
+                fixed(bytes, 4)?;
+                Self::Have(bytes.get_u32())
+            }
+            Code::Bitfield => Self::Bitfield(bytes.split_off(0)),
+            Code::Request => Self::Request(Request::decode(bytes)?),
+            Code::Piece => Self::Piece(Piece::decode(bytes)?),
+            Code::Cancel => Self::Cancel(Request::decode(bytes)?),
+            Code::Port => {
+                fixed(bytes, 2)?;
+                Self::Port(bytes.get_u16())
+            }
+            Code::Extended => Self::Extended(Extended::decode(bytes)?),
+        };
+        Ok(msg)
     }
 }
 
-impl<T> Encodable for Outgoing<T>
-where
-    T: Encodable,
-{
+impl Encodable for Message {
     fn encode(&self, buf: &mut BytesMut) {
-        self.code.encode(buf);
-        self.data.encode(buf);
+        let Some(code) = self.code() else {
+            return;
+        };
+        code.encode(buf);
+        match self {
+            Message::Have(index) => buf.put_u32(*index),
+            Message::Bitfield(bits) => buf.put_slice(bits),
+            Message::Request(r) | Message::Cancel(r) => r.encode(buf),
+            Message::Piece(p) => p.encode(buf),
+            Message::Port(port) => buf.put_u16(*port),
+            Message::Extended(e) => e.encode(buf),
+            _ => {}
+        }
     }
 }
 
 pub mod payload {
     use super::*;
 
-    #[derive(Debug)]
+    #[derive(Clone, Copy, Debug, PartialEq)]
     pub struct Request {
         pub index: u32,
         pub begin: u32,
@@ -112,14 +177,32 @@ pub mod payload {
         }
     }
 
-    #[allow(dead_code)]
-    #[derive(Debug)]
+    impl Decodable for Request {
+        fn decode(bytes: &mut Bytes) -> Result<Self> {
+            anyhow::ensure!(bytes.len() == 12, "Invalid request length");
+            Ok(Self {
+                index: bytes.get_u32(),
+                begin: bytes.get_u32(),
+                length: bytes.get_u32(),
+            })
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
     pub struct Piece {
         pub index: u32,
         pub begin: u32,
         pub data: Bytes,
     }
 
+    impl Encodable for Piece {
+        fn encode(&self, buf: &mut BytesMut) {
+            buf.put_u32(self.index);
+            buf.put_u32(self.begin);
+            buf.put_slice(&self.data);
+        }
+    }
+
     impl Decodable for Piece {
         fn decode(bytes: &mut Bytes) -> Result<Self> {
             anyhow::ensure!(bytes.len() >= 8);
@@ -132,4 +215,79 @@ pub mod payload {
             Ok(piece)
         }
     }
+
+    /// Extension message, `id` 0 being the extension handshake.
+    #[derive(Debug, PartialEq)]
+    pub struct Extended {
+        pub id: u8,
+        pub payload: Bytes,
+    }
+
+    impl Encodable for Extended {
+        fn encode(&self, buf: &mut BytesMut) {
+            buf.put_u8(self.id);
+            buf.put_slice(&self.payload);
+        }
+    }
+
+    impl Decodable for Extended {
+        fn decode(bytes: &mut Bytes) -> Result<Self> {
+            anyhow::ensure!(!bytes.is_empty());
+            let id = bytes.get_u8();
+            let payload = bytes.split_off(0);
+            Ok(Self { id, payload })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(msg: Message) {
+        let mut buf = BytesMut::new();
+        msg.encode(&mut buf);
+        assert_eq!(Message::decode(&mut buf.freeze()).unwrap(), msg);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let req = Request::new(1, 16384, 16384);
+        for msg in [
+            Message::KeepAlive,
+            Message::Choke,
+            Message::Unchoke,
+            Message::Interested,
+            Message::NotInterested,
+            Message::Have(7),
+            Message::Bitfield(Bytes::from_static(&[0b1010_0000])),
+            Message::Request(req),
+            Message::Cancel(req),
+            Message::Piece(Piece {
+                index: 1,
+                begin: 0,
+                data: Bytes::from_static(b"data"),
+            }),
+            Message::Port(6881),
+            Message::Extended(Extended {
+                id: 0,
+                payload: Bytes::from_static(b"de"),
+            }),
+        ] {
+            round_trip(msg);
+        }
+    }
+
+    #[test]
+    fn test_wire_format() {
+        let mut buf = BytesMut::new();
+        Message::Have(258).encode(&mut buf);
+        assert_eq!(&buf[..], [4, 0, 0, 1, 2]);
+        let mut bytes = Bytes::from_static(&[6, 0, 0, 0, 1]);
+        assert!(Message::decode(&mut bytes).is_err());
+        let mut bytes = Bytes::from_static(&[0, 1]);
+        assert!(Message::decode(&mut bytes).is_err());
+        let mut bytes = Bytes::from_static(&[42]);
+        assert!(Message::decode(&mut bytes).is_err());
+    }
 }
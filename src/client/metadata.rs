@@ -0,0 +1,199 @@
+use super::{Client, Stream, PEER_TIMEOUT};
+use crate::{
+    ben::{self, Ben, Options},
+    hash::Hash,
+    torrent::Torrent,
+};
+use anyhow::{bail, ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+use tokio::time::timeout;
+
+/// Size of every metadata piece but the last (BEP 9).
+const PIECE_SIZE: usize = 16 * 1024;
+/// Largest `info` dictionary accepted from a peer.
+const MAX_METADATA_SIZE: usize = 16 * 1024 * 1024;
+const UT_METADATA: &str = "ut_metadata";
+
+#[repr(u8)]
+enum MsgType {
+    Request = 0,
+    Data = 1,
+    Reject = 2,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct MetadataMessage {
+    msg_type: u8,
+    piece: usize,
+    total_size: Option<usize>,
+}
+
+impl Stream {
+    /// Downloads the `info` dictionary with `ut_metadata` (BEP 9), checking
+    /// it against `info_hash`.
+    pub async fn fetch_metadata(&mut self, info_hash: Hash) -> Result<Vec<u8>> {
+        let remote = self.extension_handshake().await?;
+        let size = remote.metadata_size.context("Peer sent no metadata size")?;
+        ensure!(
+            (1..=MAX_METADATA_SIZE).contains(&size),
+            "Invalid metadata size: {size}"
+        );
+
+        let mut data = Vec::with_capacity(size);
+        for piece in 0..size.div_ceil(PIECE_SIZE) {
+            let req = MetadataMessage {
+                msg_type: MsgType::Request as u8,
+                piece,
+                total_size: None,
+            };
+            self.write_extension(UT_METADATA, ben::to_bytes(&req)?)
+                .await?;
+            let chunk = self.read_metadata_piece(piece).await?;
+            let expected = (size - data.len()).min(PIECE_SIZE);
+            ensure!(
+                chunk.len() == expected,
+                "Metadata piece {piece} has {} bytes instead of {expected}",
+                chunk.len()
+            );
+            data.extend_from_slice(&chunk);
+        }
+
+        let hash = Hash::encode(&data)?;
+        ensure!(
+            hash == info_hash,
+            "Metadata does not match the info-hash: {hash}"
+        );
+        Ok(data)
+    }
+
+    async fn read_metadata_piece(&mut self, piece: usize) -> Result<Vec<u8>> {
+        loop {
+            let payload = self.read_extension(UT_METADATA).await?;
+            // The bencoded header is directly followed by the piece data.
+            let (header, used) = Ben::decode_prefix(&payload, Options::untrusted())?;
+            let msg: MetadataMessage = ben::from_value(&header.value)?;
+            match msg.msg_type {
+                t if t == MsgType::Data as u8 && msg.piece == piece => {
+                    return Ok(payload[used..].to_vec())
+                }
+                t if t == MsgType::Reject as u8 => bail!("Peer rejected metadata piece {piece}"),
+                _ => continue,
+            }
+        }
+    }
+}
+
+impl Client {
+    /// The torrent metadata, fetched from peers when opened from a magnet.
+    pub async fn metadata(&mut self) -> Result<&Torrent> {
+        if self.torrent.is_none() {
+            let info = self.fetch_metadata().await?;
+            self.torrent = Some(Torrent::from_info(&info, &self.trackers)?);
+        }
+        self.torrent()
+    }
+
+    /// Raw `info` dictionary from the first peer able to provide it.
+    async fn fetch_metadata(&mut self) -> Result<Vec<u8>> {
+        let peers = self.discover_peers().await?.clone();
+        let mut error = anyhow::anyhow!("No peers available");
+        for peer in peers {
+            let fetch = async {
+                let mut stream = self.connect(peer).await?;
+                stream.fetch_metadata(self.info_hash).await
+            };
+            let fetched = timeout(PEER_TIMEOUT, fetch).await;
+            match fetched.unwrap_or_else(|_| Err(anyhow::anyhow!("Timed out"))) {
+                Ok(info) => return Ok(info),
+                Err(e) => error = e.context(format!("Peer {peer}")),
+            }
+        }
+        Err(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{
+        extension::ExtensionHandshake,
+        message::{payload::Extended, Message},
+        testing::{accept, listen, receive, send},
+        Source,
+    };
+    use tokio::net::{TcpListener, TcpStream};
+
+    async fn send_extended(s: &mut TcpStream, id: u8, payload: Vec<u8>) {
+        let payload = payload.into();
+        send(s, Message::Extended(Extended { id, payload })).await;
+    }
+
+    async fn receive_extended(s: &mut TcpStream) -> Extended {
+        match receive(s).await {
+            Message::Extended(ext) => ext,
+            msg => panic!("Expected an extension message: {msg:?}"),
+        }
+    }
+
+    /// Peer serving `info` over `ut_metadata` with id 3.
+    async fn serve(listener: TcpListener, info: Vec<u8>) {
+        let mut s = accept(listener).await;
+
+        let ext = receive_extended(&mut s).await;
+        assert_eq!(ext.id, 0);
+        let ours: ExtensionHandshake =
+            ben::from_value(&Ben::try_from(&ext.payload[..]).unwrap()).unwrap();
+        assert_eq!(ours.id(UT_METADATA), Some(1));
+        let mut remote = ExtensionHandshake {
+            metadata_size: Some(info.len()),
+            ..Default::default()
+        };
+        remote.m.insert(UT_METADATA.into(), 3);
+        send_extended(&mut s, 0, ben::to_bytes(&remote).unwrap()).await;
+
+        for chunk in info.chunks(PIECE_SIZE).enumerate() {
+            let ext = receive_extended(&mut s).await;
+            assert_eq!(ext.id, 3);
+            let req: MetadataMessage =
+                ben::from_value(&Ben::try_from(&ext.payload[..]).unwrap()).unwrap();
+            assert_eq!(req.piece, chunk.0);
+            let header = MetadataMessage {
+                msg_type: MsgType::Data as u8,
+                piece: chunk.0,
+                total_size: Some(info.len()),
+            };
+            let mut payload = ben::to_bytes(&header).unwrap();
+            payload.extend_from_slice(chunk.1);
+            send_extended(&mut s, 1, payload).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fetch_from_magnet() {
+        let json = serde_json::json!({
+            "length": 92063, "name": "sample.txt", "piece length": 32768,
+            "pieces": {"$hex": "ab".repeat(20 * 1000)},
+        });
+        let info = Ben::try_from(&json).unwrap().to_bytes();
+        assert!(info.len() > PIECE_SIZE);
+        let hash = Hash::encode(&info).unwrap();
+
+        let (listener, port) = listen().await;
+        let server = tokio::spawn(serve(listener, info));
+
+        let link = format!(
+            "magnet:?xt=urn:btih:{}&tr=http%3A%2F%2Ftracker%2Fannounce&x.pe=127.0.0.1:{port}",
+            hash.digest()
+        );
+        let mut client = Client::open(&link.parse::<Source>().unwrap()).unwrap();
+        let t = client.metadata().await.unwrap();
+        assert_eq!(t.info.name, "sample.txt");
+        assert_eq!(t.info.piece_count(), 1000);
+        assert_eq!(t.info_hash().unwrap(), hash);
+        assert_eq!(
+            t.announce.as_ref().unwrap().as_str(),
+            "http://tracker/announce"
+        );
+        server.await.unwrap();
+    }
+}
@@ -0,0 +1,133 @@
+use super::{message::Message, stream::MAX_MESSAGE_LENGTH};
+use anyhow::{ensure, Result};
+use bytes::Bytes;
+
+/// Pieces a peer has, high bit of the first byte being piece 0.
+#[derive(Debug)]
+pub struct Bitfield {
+    bits: Vec<u8>,
+    /// Piece count of the torrent, when known, bounding the bitfield.
+    pieces: Option<usize>,
+}
+
+impl Bitfield {
+    fn new(pieces: Option<usize>) -> Self {
+        Self {
+            bits: vec![0; pieces.map_or(0, |p| p.div_ceil(8))],
+            pieces,
+        }
+    }
+
+    pub fn has(&self, index: u32) -> bool {
+        let (byte, bit) = (index as usize / 8, index % 8);
+        self.bits.get(byte).is_some_and(|b| b & (0x80 >> bit) != 0)
+    }
+
+    /// Marks a piece as available, ignoring indices past the last piece, or
+    /// past the largest bitfield a peer could send while the count is unknown.
+    fn set(&mut self, index: u32) {
+        let limit = self.pieces.unwrap_or(MAX_MESSAGE_LENGTH as usize * 8);
+        if index as usize >= limit {
+            return;
+        }
+        let (byte, bit) = (index as usize / 8, index % 8);
+        if self.bits.len() <= byte {
+            self.bits.resize(byte + 1, 0);
+        }
+        self.bits[byte] |= 0x80 >> bit;
+    }
+
+    fn replace(&mut self, bytes: &Bytes) -> Result<()> {
+        if let Some(pieces) = self.pieces {
+            let len = pieces.div_ceil(8);
+            ensure!(
+                bytes.len() == len,
+                "Bitfield has {} bytes instead of {len}",
+                bytes.len()
+            );
+        }
+        self.bits = bytes.to_vec();
+        Ok(())
+    }
+}
+
+/// Choke and interest flags of both sides of a connection, as described by
+/// the peer wire protocol. Connections start choked and not interested.
+#[derive(Debug)]
+pub struct State {
+    pub am_choking: bool,
+    pub am_interested: bool,
+    pub peer_choking: bool,
+    pub peer_interested: bool,
+    pub bitfield: Bitfield,
+}
+
+impl State {
+    /// Initial state of a connection for a torrent of `pieces` pieces, if
+    /// known yet.
+    pub fn new(pieces: Option<usize>) -> Self {
+        Self {
+            am_choking: true,
+            am_interested: false,
+            peer_choking: true,
+            peer_interested: false,
+            bitfield: Bitfield::new(pieces),
+        }
+    }
+
+    /// Applies a message received from the peer.
+    pub fn received(&mut self, msg: &Message) -> Result<()> {
+        match msg {
+            Message::Choke => self.peer_choking = true,
+            Message::Unchoke => self.peer_choking = false,
+            Message::Interested => self.peer_interested = true,
+            Message::NotInterested => self.peer_interested = false,
+            Message::Have(index) => self.bitfield.set(*index),
+            Message::Bitfield(bits) => self.bitfield.replace(bits)?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Applies a message sent to the peer.
+    pub fn sent(&mut self, msg: &Message) {
+        match msg {
+            Message::Choke => self.am_choking = true,
+            Message::Unchoke => self.am_choking = false,
+            Message::Interested => self.am_interested = true,
+            Message::NotInterested => self.am_interested = false,
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state() {
+        let mut s = State::new(Some(10));
+        assert!(s.am_choking && s.peer_choking);
+        let bits = Bytes::from_static(&[0b0100_0000, 0]);
+        s.received(&Message::Bitfield(bits)).unwrap();
+        s.received(&Message::Have(9)).unwrap();
+        s.received(&Message::Have(10)).unwrap();
+        s.received(&Message::Have(u32::MAX)).unwrap();
+        s.received(&Message::Unchoke).unwrap();
+        s.sent(&Message::Interested);
+        assert!(!s.bitfield.has(0) && s.bitfield.has(1) && s.bitfield.has(9));
+        assert!(!s.bitfield.has(10) && !s.bitfield.has(u32::MAX));
+        assert!(!s.peer_choking && s.am_interested);
+        s.received(&Message::Choke).unwrap();
+        assert!(s.peer_choking);
+
+        let bits = Bytes::from_static(&[0xff; 3]);
+        assert!(s.received(&Message::Bitfield(bits)).is_err());
+
+        let mut s = State::new(None);
+        s.received(&Message::Have(9)).unwrap();
+        s.received(&Message::Have(u32::MAX)).unwrap();
+        assert!(s.bitfield.has(9) && s.bitfield.bits.len() == 2);
+    }
+}
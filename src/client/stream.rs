@@ -1,11 +1,13 @@
 use super::{
-    message::{Code, Incoming, Outgoing},
+    extension::{ExtensionHandshake, Registry},
+    message::{payload::Extended, Message},
+    state::State,
     Client, Peer,
 };
 use crate::hash::Hash;
 use anyhow::Result;
 use bytes::{BufMut, Bytes, BytesMut};
-use std::net::SocketAddrV4;
+use std::{collections::VecDeque, net::SocketAddrV4};
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt},
     net::TcpStream,
@@ -13,33 +15,52 @@ use tokio::{
 
 const PROTOCOL_ID: u8 = 19;
 const PROTOCOL_NAME: &[u8] = b"BitTorrent protocol";
-const RESERVED: [u8; 8] = [0u8; 8];
+/// Reserved bit advertising the extension protocol (BEP 10).
+const EXTENSION_BIT: (usize, u8) = (5, 0x10);
+/// Longest message accepted from a peer, which fits a block, a metadata piece
+/// and the bitfield of any torrent whose metadata we accept.
+pub(super) const MAX_MESSAGE_LENGTH: u32 = 1024 * 1024;
 
 pub struct Stream {
-    #[allow(unused)]
-    stream: TcpStream,
+    pub(super) stream: TcpStream,
     pub peer_id: Hash,
+    /// Whether the peer supports the extension protocol.
+    pub extensions: bool,
+    pub(super) registry: Registry,
+    /// Extension handshake of the peer, once exchanged.
+    pub(super) remote: Option<ExtensionHandshake>,
+    /// Extension messages received while reading another extension.
+    pub(super) queued: VecDeque<Extended>,
+    pub state: State,
 }
 
 #[derive(Debug)]
 struct Handshake {
+    reserved: [u8; 8],
     info_hash: Hash,
     peer_id: Hash,
 }
 
 impl Handshake {
     fn new(id: Hash, info: Hash) -> Self {
+        let mut reserved = [0u8; 8];
+        reserved[EXTENSION_BIT.0] |= EXTENSION_BIT.1;
         Self {
+            reserved,
             info_hash: info,
             peer_id: id,
         }
     }
 
+    fn extensions(&self) -> bool {
+        self.reserved[EXTENSION_BIT.0] & EXTENSION_BIT.1 != 0
+    }
+
     fn to_bytes(&self) -> Bytes {
         let mut buff = BytesMut::with_capacity(68);
         buff.put_u8(PROTOCOL_ID);
         buff.put_slice(PROTOCOL_NAME);
-        buff.put_slice(&RESERVED);
+        buff.put_slice(&self.reserved);
         buff.put_slice(self.info_hash.as_bytes());
         buff.put_slice(self.peer_id.as_bytes());
         buff.freeze()
@@ -50,6 +71,7 @@ impl Handshake {
         anyhow::ensure!(bytes[0] == PROTOCOL_ID);
         anyhow::ensure!(&bytes[1..20] == PROTOCOL_NAME);
         Ok(Self {
+            reserved: bytes[20..28].try_into()?,
             info_hash: Hash::new(bytes[28..=47].try_into()?),
             peer_id: Hash::new(bytes[48..=67].try_into()?),
         })
@@ -58,22 +80,26 @@ impl Handshake {
 
 impl Stream {
     pub async fn open(c: &Client, p: Peer) -> Result<Self> {
-        let info = c.torrent.info.hash()?;
-        let hs = Handshake::new(c.id, info);
+        let hs = Handshake::new(c.id, c.info_hash);
         let chunk = hs.to_bytes();
         let addr: SocketAddrV4 = p.into();
 
         let mut stream = TcpStream::connect(addr).await?;
         stream.write_all(&chunk).await?;
 
-        let mut buf = BytesMut::with_capacity(68);
-        stream.read_buf(&mut buf).await?;
+        let mut buf = [0u8; 68];
+        stream.read_exact(&mut buf).await?;
 
-        let bytes = buf.freeze();
-        let hs = Handshake::from_bytes(&bytes)?;
+        let hs = Handshake::from_bytes(&buf)?;
+        anyhow::ensure!(hs.info_hash == c.info_hash, "Peer sent another info-hash");
         Ok(Self {
             stream,
             peer_id: hs.peer_id,
+            extensions: hs.extensions(),
+            registry: c.registry.clone(),
+            remote: None,
+            queued: VecDeque::new(),
+            state: State::new(c.torrent.as_ref().map(|t| t.info.piece_count())),
         })
     }
 }
@@ -84,43 +110,26 @@ pub trait Decodable: Sized {
 
 pub trait Encodable: Sized {
     fn encode(&self, buf: &mut BytesMut);
-    fn len(&self) -> usize {
-        std::mem::size_of::<Self>()
-    }
 }
 
 impl Stream {
-    pub async fn read(&mut self) -> Result<Incoming> {
+    /// Next message from the peer, applied to the connection state.
+    pub async fn read(&mut self) -> Result<Message> {
         let len = self.stream.read_u32().await?;
-        let mut bytes = BytesMut::with_capacity(len as usize);
-        loop {
-            let read = self.stream.read_buf(&mut bytes).await?;
-            if read == 0 || bytes.len() == len as usize {
-                break;
-            }
-        }
-        Incoming::decode(&mut bytes.freeze())
+        anyhow::ensure!(len <= MAX_MESSAGE_LENGTH, "Message too long: {len} bytes");
+        let mut bytes = vec![0; len as usize];
+        self.stream.read_exact(&mut bytes).await?;
+        let msg = Message::decode(&mut Bytes::from(bytes))?;
+        self.state.received(&msg)?;
+        Ok(msg)
     }
 
-    pub async fn read_code(&mut self, c: Code) -> Result<()> {
-        let msg: Incoming = self.read().await?;
-        anyhow::ensure!(msg.code == c, "Unexpected code: {} != {}", c, msg.code);
-        Ok(())
-    }
-
-    async fn write(&mut self, msg: &impl Encodable) -> Result<()> {
-        let mut buf = BytesMut::with_capacity(msg.len());
-        self.stream.write_u32(msg.len() as u32).await?;
+    pub async fn write(&mut self, msg: &Message) -> Result<()> {
+        let mut buf = BytesMut::new();
         msg.encode(&mut buf);
+        self.stream.write_u32(buf.len() as u32).await?;
         self.stream.write_all(&buf).await?;
+        self.state.sent(msg);
         Ok(())
     }
-
-    pub async fn write_message(&mut self, out: &Outgoing<impl Encodable>) -> Result<()> {
-        self.write(out).await
-    }
-
-    pub async fn write_code(&mut self, code: Code) -> Result<()> {
-        self.write(&code).await
-    }
 }
@@ -0,0 +1,40 @@
+use super::{
+    message::Message,
+    stream::{Decodable, Encodable},
+};
+use bytes::{Bytes, BytesMut};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+/// Listener for a fake remote peer, and the port to reach it.
+pub async fn listen() -> (TcpListener, u16) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    (listener, port)
+}
+
+/// Accepts a connection, answering the handshake with a remote peer id.
+pub async fn accept(listener: TcpListener) -> TcpStream {
+    let (mut s, _) = listener.accept().await.unwrap();
+    let mut hs = [0u8; 68];
+    s.read_exact(&mut hs).await.unwrap();
+    hs[48..].copy_from_slice(b"-XX0000-remote-peer-");
+    s.write_all(&hs).await.unwrap();
+    s
+}
+
+pub async fn send(s: &mut TcpStream, msg: Message) {
+    let mut buf = BytesMut::new();
+    msg.encode(&mut buf);
+    s.write_u32(buf.len() as u32).await.unwrap();
+    s.write_all(&buf).await.unwrap();
+}
+
+pub async fn receive(s: &mut TcpStream) -> Message {
+    let len = s.read_u32().await.unwrap();
+    let mut buf = vec![0; len as usize];
+    s.read_exact(&mut buf).await.unwrap();
+    Message::decode(&mut Bytes::from(buf)).unwrap()
+}
@@ -1,6 +1,8 @@
+mod parallel;
 use std::fmt::Display;
 
 use anyhow::Result;
+pub use parallel::hash_pieces;
 use sha1::{Digest, Sha1};
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -0,0 +1,82 @@
+use crate::hash::Hash;
+use anyhow::Result;
+use std::{
+    io::Read,
+    sync::{
+        mpsc::{channel, sync_channel, Receiver},
+        Mutex,
+    },
+    thread,
+};
+
+/// Hashes `reader` in pieces of `piece_length` bytes, the last one possibly
+/// shorter, returning the hashes in order.
+///
+/// A reader thread feeds a bounded queue of pieces to one worker per core, so
+/// reading and hashing overlap and only a few pieces per worker are buffered.
+pub fn hash_pieces(mut reader: impl Read + Send, piece_length: usize) -> Result<Vec<Hash>> {
+    anyhow::ensure!(piece_length > 0, "Piece length must be greater than zero");
+    let workers = thread::available_parallelism().map_or(1, |n| n.get());
+    let (work_tx, work_rx) = sync_channel::<(usize, Vec<u8>)>(workers * 2);
+    let work_rx = Mutex::new(work_rx);
+    let (done_tx, done_rx) = channel();
+
+    let (read, mut hashes) = thread::scope(|s| {
+        let read = s.spawn(move || -> Result<()> {
+            for index in 0.. {
+                let mut piece = Vec::with_capacity(piece_length);
+                (&mut reader)
+                    .take(piece_length as u64)
+                    .read_to_end(&mut piece)?;
+                let last = piece.len() < piece_length;
+                if piece.is_empty() || work_tx.send((index, piece)).is_err() || last {
+                    break;
+                }
+            }
+            Ok(())
+        });
+        for _ in 0..workers {
+            let (rx, tx) = (&work_rx, done_tx.clone());
+            s.spawn(move || {
+                while let Some((index, piece)) = next(rx) {
+                    if tx.send((index, Hash::encode(piece))).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(done_tx);
+        let hashes: Vec<_> = done_rx.iter().collect();
+        (read.join(), hashes)
+    });
+
+    read.map_err(|_| anyhow::anyhow!("Reader thread panicked"))??;
+    hashes.sort_by_key(|h| h.0);
+    hashes.into_iter().map(|h| h.1).collect()
+}
+
+/// Next queued piece, holding the lock only while waiting for it.
+fn next<T>(rx: &Mutex<Receiver<T>>) -> Option<T> {
+    rx.lock().ok()?.recv().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ordered() {
+        let data: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        let hashes = hash_pieces(data.as_slice(), 1000).unwrap();
+        let expected: Vec<_> = data
+            .chunks(1000)
+            .map(|c| Hash::encode(c).unwrap())
+            .collect();
+        assert_eq!(hashes, expected);
+
+        let hashes = hash_pieces(&data[..2500], 1000).unwrap();
+        assert_eq!(hashes.len(), 3);
+        assert_eq!(hashes[2], Hash::encode(&data[2000..2500]).unwrap());
+        assert!(hash_pieces(&[][..], 1000).unwrap().is_empty());
+    }
+}
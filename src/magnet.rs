@@ -0,0 +1,136 @@
+use crate::{client::Peer, hash::Hash};
+use anyhow::{bail, ensure, Context, Result};
+use reqwest::Url;
+use std::str::FromStr;
+
+const SCHEME: &str = "magnet:?";
+const BTIH: &str = "urn:btih:";
+
+/// What a magnet link tells about a torrent: enough to find peers, but not
+/// its metadata.
+#[derive(Clone, Debug)]
+pub struct Magnet {
+    pub info_hash: Hash,
+    /// Display name, `dn`.
+    pub name: Option<String>,
+    /// Tracker URLs, `tr`.
+    pub trackers: Vec<Url>,
+    /// Peer addresses, `x.pe`. Only IPv4 ones are kept.
+    pub peers: Vec<Peer>,
+    /// Web seeds, `ws`.
+    pub web_seeds: Vec<String>,
+}
+
+impl FromStr for Magnet {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self> {
+        let query = s.strip_prefix(SCHEME).context("Not a magnet link")?;
+        let params: Vec<(String, String)> = serde_urlencoded::from_str(query)?;
+        let mut info_hash = None;
+        let (mut name, mut trackers, mut peers, mut web_seeds) = (None, vec![], vec![], vec![]);
+        for (key, value) in params {
+            match key.as_str() {
+                "xt" => {
+                    if let Some(hash) = value.strip_prefix(BTIH) {
+                        info_hash = Some(parse_btih(hash)?);
+                    }
+                }
+                "dn" => name = Some(value),
+                // Unusable trackers and peers are skipped, others may do.
+                "tr" => trackers.extend(value.parse::<Url>().ok()),
+                "x.pe" => peers.extend(value.parse::<Peer>().ok()),
+                "ws" => web_seeds.push(value),
+                _ => {}
+            }
+        }
+        Ok(Self {
+            info_hash: info_hash.context("Magnet link without a urn:btih topic")?,
+            name,
+            trackers,
+            peers,
+            web_seeds,
+        })
+    }
+}
+
+/// Info-hash given as 40 hex digits or 32 base32 characters.
+fn parse_btih(s: &str) -> Result<Hash> {
+    let bytes = match s.len() {
+        40 => hex::decode(s)?,
+        32 => base32(s)?,
+        n => bail!("Invalid info-hash length: {n}"),
+    };
+    Ok(Hash::new(bytes.as_slice().try_into()?))
+}
+
+/// Decodes unpadded RFC 4648 base32.
+fn base32(s: &str) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(s.len() * 5 / 8);
+    let (mut bits, mut acc) = (0, 0u32);
+    for c in s.bytes() {
+        let v = match c.to_ascii_uppercase() {
+            c @ b'A'..=b'Z' => c - b'A',
+            c @ b'2'..=b'7' => c - b'2' + 26,
+            _ => bail!("Invalid base32 character: {:?}", c as char),
+        };
+        acc = (acc << 5) | v as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+        }
+    }
+    ensure!(acc & ((1 << bits) - 1) == 0, "Invalid base32 padding bits");
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASH: &str = "d69f91e6b2ae4c542468d1073a71d4ea13879a7f";
+
+    #[test]
+    fn test_hex() {
+        let link = format!(
+            "magnet:?xt=urn:btih:{HASH}&dn=sample.torrent\
+             &tr=http%3A%2F%2Fbittorrent-test-tracker.codecrafters.io%2Fannounce\
+             &tr=not%20a%20url\
+             &x.pe=1.2.3.4:6881&x.pe=peer.example:1&ws=http%3A%2F%2Fseed%2F"
+        );
+        let m: Magnet = link.parse().unwrap();
+        assert_eq!(m.info_hash.digest(), HASH);
+        assert_eq!(m.name.as_deref(), Some("sample.torrent"));
+        assert_eq!(m.trackers.len(), 1);
+        assert_eq!(
+            m.trackers[0].as_str(),
+            "http://bittorrent-test-tracker.codecrafters.io/announce"
+        );
+        assert_eq!(m.peers.len(), 1);
+        assert_eq!(m.peers[0].to_string(), "1.2.3.4:6881");
+        assert_eq!(m.web_seeds, vec!["http://seed/"]);
+    }
+
+    #[test]
+    fn test_base32() {
+        let bytes = hex::decode(HASH).unwrap();
+        assert_eq!(base32("MZXW6YTBOI").unwrap(), b"foobar");
+        let m: Magnet = "magnet:?xt=urn:btih:22PZDZVSVZGFIJDI2EDTU4OU5IJYPGT7"
+            .parse()
+            .unwrap();
+        assert_eq!(m.info_hash.as_bytes(), bytes);
+        assert!(m.trackers.is_empty());
+    }
+
+    #[test]
+    fn test_invalid() {
+        for link in [
+            "http://example.com",
+            "magnet:?dn=x",
+            "magnet:?xt=urn:btih:abc",
+            "magnet:?xt=urn:btih:22PZDZVSVZGFIJDI2EDTU4OU5IJYPGT1",
+        ] {
+            assert!(link.parse::<Magnet>().is_err(), "{link} should be invalid");
+        }
+    }
+}
@@ -2,14 +2,15 @@ mod args;
 mod ben;
 mod client;
 mod hash;
+mod magnet;
 mod torrent;
 use anyhow::Result;
 use args::Command;
-use ben::Ben;
-use client::Client;
-use client::Peer;
-use std::path::Path;
-use torrent::Torrent;
+use ben::{Ben, Mode, Options, Query};
+use client::{Client, Peer, Source, PEER_TIMEOUT};
+use magnet::Magnet;
+use std::{io::Write, path::Path};
+use torrent::{Builder, Level, Torrent};
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -19,10 +20,55 @@ async fn main() -> Result<()> {
     };
 
     match cmd {
-        Command::Decode { input } => handle_decode(input),
-        Command::Info { path } => handle_info(&path),
-        Command::Peers { path } => handle_peers(&path).await,
-        Command::Handshake { path, peer } => handle_handshake(&path, peer).await,
+        Command::Decode {
+            input,
+            file,
+            strict,
+            spans,
+            query,
+            pretty,
+        } => {
+            let mode = if strict { Mode::Strict } else { Mode::Lenient };
+            let options = Options {
+                mode,
+                spans,
+                ..Default::default()
+            };
+            handle_decode(input, file.as_deref(), options, query, pretty)
+        }
+        Command::Encode { input, output } => handle_encode(input, output.as_deref()),
+        Command::Diff { left, right } => handle_diff(&left, &right),
+        Command::Info { path, raw } => handle_info(&path, raw),
+        Command::Validate { path } => handle_validate(&path),
+        Command::Verify { torrent, path } => handle_verify(&torrent, &path),
+        Command::Create {
+            path,
+            output,
+            announce,
+            comment,
+            private,
+            web_seed,
+            piece_length,
+        } => {
+            let mut builder = Builder::new(&path).private(private);
+            for url in announce {
+                builder = builder.tracker(url);
+            }
+            for url in web_seed {
+                builder = builder.web_seed(url);
+            }
+            if let Some(comment) = comment {
+                builder = builder.comment(comment);
+            }
+            if let Some(length) = piece_length {
+                builder = builder.piece_length(length);
+            }
+            handle_create(builder, output.as_deref())
+        }
+        Command::MagnetParse { link } => handle_magnet_parse(&link),
+        Command::MagnetInfo { link } => handle_magnet_info(link).await,
+        Command::Peers { source } => handle_peers(&source).await,
+        Command::Handshake { source, peer } => handle_handshake(&source, peer).await,
         Command::DownloadPiece {
             output,
             torrent,
@@ -32,50 +78,239 @@ async fn main() -> Result<()> {
     }
 }
 
-async fn handle_peers(p: &Path) -> Result<()> {
-    let mut client = Client::open(p)?;
+fn handle_magnet_parse(m: &Magnet) -> Result<()> {
+    for url in &m.trackers {
+        println!("Tracker URL: {url}");
+    }
+    println!("Info Hash: {}", m.info_hash.digest());
+    if let Some(name) = &m.name {
+        println!("Name: {name}");
+    }
+    for peer in &m.peers {
+        println!("Peer: {peer}");
+    }
+    for url in &m.web_seeds {
+        println!("Web Seed: {url}");
+    }
+    Ok(())
+}
+
+async fn handle_magnet_info(m: Magnet) -> Result<()> {
+    let mut client = Client::open(&Source::Magnet(m))?;
+    print_info(client.metadata().await?)
+}
+
+async fn handle_peers(source: &Source) -> Result<()> {
+    let mut client = Client::open(source)?;
     for peer in client.discover_peers().await? {
         println!("{peer}")
     }
     Ok(())
 }
 
-fn handle_decode(i: String) -> Result<()> {
-    let ben: Ben = i.parse()?;
-    println!("{ben}");
+fn handle_decode(
+    i: Option<String>,
+    file: Option<&Path>,
+    options: Options,
+    query: Option<Query>,
+    pretty: bool,
+) -> Result<()> {
+    let data = match (i, file) {
+        (_, Some(p)) => std::fs::read(p)?,
+        (Some(i), None) => i.into_bytes(),
+        (None, None) => anyhow::bail!("No input to decode."),
+    };
+    let decoded = Ben::decode_with(&data, options)?;
+    for w in decoded.warnings {
+        eprintln!("warning: {w}");
+    }
+    let nodes = match &query {
+        Some(q) => decoded.value.query(q).into_iter().map(|m| m.1).collect(),
+        None => vec![&decoded.value],
+    };
+    for b in nodes {
+        match pretty {
+            true => println!("{}", b.pretty()),
+            false => println!("{b}"),
+        }
+    }
+    for (path, span) in decoded.spans.iter() {
+        println!("{}..{} {path}", span.start, span.end);
+    }
     Ok(())
 }
 
-fn handle_info(p: &Path) -> Result<()> {
-    let t = Torrent::open(p)?;
-    println!("Tracker URL: {}", t.announce);
-    println!("Length: {}", t.info.length);
-    println!("Info Hash: {}", t.info.hash()?.digest());
+fn handle_encode(i: Option<String>, out: Option<&Path>) -> Result<()> {
+    let json = match i {
+        Some(i) if i != "-" => i,
+        _ => std::io::read_to_string(std::io::stdin())?,
+    };
+    let value: serde_json::Value = serde_json::from_str(&json)?;
+    let bytes = Ben::try_from(&value)?.to_bytes();
+    match out {
+        Some(p) => std::fs::write(p, bytes)?,
+        None => std::io::stdout().write_all(&bytes)?,
+    }
+    Ok(())
+}
+
+fn handle_diff(left: &Path, right: &Path) -> Result<()> {
+    let left = Ben::try_from(std::fs::read(left)?.as_slice())?;
+    let right = Ben::try_from(std::fs::read(right)?.as_slice())?;
+    let changes = left.diff(&right);
+    if changes.is_empty() {
+        println!("No differences.");
+    }
+    for c in changes {
+        println!("{c}");
+    }
+    Ok(())
+}
+
+fn handle_info(p: &Path, raw: bool) -> Result<()> {
+    if raw {
+        let ben = Ben::try_from(std::fs::read(p)?.as_slice())?;
+        println!("{}", ben.pretty());
+        return Ok(());
+    }
+    print_info(&Torrent::open(p)?)
+}
+
+fn print_info(t: &Torrent) -> Result<()> {
+    if let Some(announce) = &t.announce {
+        println!("Tracker URL: {announce}");
+    }
+    println!("Length: {}", t.info.length()?);
+    println!("Info Hash: {}", t.info_hash()?.digest());
     println!("Piece Length: {}", t.info.piece_length);
     println!("Piece Hashes:");
     for digest in t.info.pieces().map(|p| p.digest()) {
         println!("{digest}")
     }
+    if t.info.is_multi_file() {
+        println!("Files:");
+        for f in t.info.files()? {
+            println!("{} ({} bytes)", f.path.display(), f.length);
+        }
+    }
+    for (i, tier) in t.announce_list.iter().enumerate() {
+        println!("Tracker Tier {}: {}", i + 1, tier.join(", "));
+    }
+    if let Some(comment) = &t.comment {
+        println!("Comment: {comment}");
+    }
+    if let Some(created_by) = &t.created_by {
+        println!("Created By: {created_by}");
+    }
+    if let Some(date) = t.creation_date {
+        println!("Creation Date: {date}");
+    }
+    if let Some(encoding) = &t.encoding {
+        println!("Encoding: {encoding}");
+    }
+    for url in t.url_list.iter().chain(&t.httpseeds) {
+        println!("Web Seed: {url}");
+    }
+    for (host, port) in &t.nodes {
+        println!("DHT Node: {host}:{port}");
+    }
+    if t.info.is_private() {
+        println!("Private: yes");
+    }
+    if let Some(source) = &t.info.source {
+        println!("Source: {source}");
+    }
+    for (key, value) in &t.extra {
+        println!("{key}: {value}");
+    }
+    Ok(())
+}
+
+fn handle_create(builder: Builder, output: Option<&Path>) -> Result<()> {
+    let t = builder.build()?;
+    let output = match output {
+        Some(p) => p.to_path_buf(),
+        None => format!("{}.torrent", t.info.name).into(),
+    };
+    std::fs::write(&output, t.to_bytes()?)?;
+    println!("Created: {}", output.display());
+    println!("Info Hash: {}", t.info_hash()?.digest());
     Ok(())
 }
 
-async fn handle_handshake(path: &Path, peer: Peer) -> Result<()> {
-    let client = Client::open(path)?;
-    let stream = client.connect(peer).await?;
+fn handle_verify(torrent: &Path, content: &Path) -> Result<()> {
+    let t = Torrent::open(torrent)?;
+    let bad = t.verify(content)?;
+    let total = t.info.piece_count();
+    println!("{}/{total} pieces OK", total - bad.len());
+    for index in &bad {
+        println!("Bad piece: {index}");
+    }
+    anyhow::ensure!(bad.is_empty(), "{} piece(s) failed verification", bad.len());
+    Ok(())
+}
+
+fn handle_validate(p: &Path) -> Result<()> {
+    let t = Torrent::open(p)?;
+    let diagnostics = t.validate();
+    for d in &diagnostics {
+        println!("{d}");
+    }
+    let errors = diagnostics
+        .iter()
+        .filter(|d| d.level == Level::Error)
+        .count();
+    anyhow::ensure!(errors == 0, "{errors} error(s) found");
+    if diagnostics.is_empty() {
+        println!("OK");
+    }
+    Ok(())
+}
+
+async fn handle_handshake(source: &Source, peer: Peer) -> Result<()> {
+    let client = Client::open(source)?;
+    let mut stream = client.connect(peer).await?;
     println!("Peer ID: {}", stream.peer_id.digest());
+    if !stream.extensions {
+        return Ok(());
+    }
+    // The core handshake is done, don't hang on peers that never follow up.
+    let Ok(remote) = tokio::time::timeout(PEER_TIMEOUT, stream.extension_handshake()).await else {
+        eprintln!("Peer sent no extension handshake");
+        return Ok(());
+    };
+    let remote = remote?;
+    if let Some(id) = remote.id("ut_metadata") {
+        println!("Peer Metadata Extension ID: {id}");
+    }
+    if let Some(v) = &remote.v {
+        println!("Peer Client: {v}");
+    }
+    for (name, id) in remote.m.iter().filter(|e| *e.1 != 0) {
+        println!("Peer Extension: {name} ({id})");
+    }
+    if let Some(p) = remote.p {
+        println!("Peer Port: {p}");
+    }
+    if let Some(reqq) = remote.reqq {
+        println!("Peer Request Queue: {reqq}");
+    }
+    if let Some(ip) = remote.yourip() {
+        println!("Our Address: {ip}");
+    }
     Ok(())
 }
 
-async fn download_piece(out: &Path, t: &Path, index: u32) -> Result<()> {
+async fn download_piece(out: &Path, t: &Source, index: u32) -> Result<()> {
     let mut client = Client::open(t)?;
     client.download_piece(index, out).await?;
     println!("Piece {index} downloaded to {}.", out.display());
     Ok(())
 }
 
-async fn download(out: &Path, t: &Path) -> Result<()> {
+async fn download(out: &Path, t: &Source) -> Result<()> {
     let mut client = Client::open(t)?;
     client.download(out).await?;
-    println!("Downloaded {} to {}.", t.display(), out.display());
+    println!("Downloaded {t} to {}.", out.display());
     Ok(())
 }
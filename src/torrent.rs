@@ -1,35 +1,250 @@
+mod content;
+mod create;
 mod info;
-use anyhow::Result;
-use info::Info;
+mod validate;
+use crate::{
+    ben::{self, Ben, Options},
+    hash::Hash,
+};
+use anyhow::{Context, Result};
+pub use create::Builder;
+pub use info::Info;
 use reqwest::Url;
-use serde::{Deserialize, Deserializer};
-use std::path::Path;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{collections::BTreeMap, path::Path};
+pub use validate::Level;
 
-#[derive(Deserialize, PartialEq, Eq, Debug)]
+/// Usual range of piece lengths, outside of which clients may choke on them.
+const MIN_PIECE_LENGTH: u64 = 16 * 1024;
+const MAX_PIECE_LENGTH: u64 = 64 * 1024 * 1024;
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
 pub struct Torrent {
-    #[serde(with = "url")]
-    pub announce: Url,
+    /// Main tracker, missing from trackerless torrents.
+    #[serde(default, with = "url", skip_serializing_if = "Option::is_none")]
+    pub announce: Option<Url>,
+    /// Tiers of tracker URLs (BEP 12).
+    #[serde(
+        rename = "announce-list",
+        default,
+        deserialize_with = "lenient::list",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub announce_list: Vec<Vec<String>>,
+    #[serde(default, deserialize_with = "lenient::text")]
+    pub comment: Option<String>,
+    #[serde(rename = "created by", default, deserialize_with = "lenient::text")]
+    pub created_by: Option<String>,
+    /// Seconds since the Unix epoch.
+    #[serde(rename = "creation date", default, deserialize_with = "lenient::value")]
+    pub creation_date: Option<i64>,
+    #[serde(default, deserialize_with = "lenient::text")]
+    pub encoding: Option<String>,
+    /// Web seeds (BEP 19), given either as a single URL or a list.
+    #[serde(
+        rename = "url-list",
+        default,
+        with = "url_list",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub url_list: Vec<String>,
+    /// HTTP seeds (BEP 17).
+    #[serde(
+        default,
+        deserialize_with = "lenient::list",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub httpseeds: Vec<String>,
+    /// DHT bootstrap nodes (BEP 5), as host and port.
+    #[serde(
+        default,
+        deserialize_with = "lenient::list",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub nodes: Vec<(String, u16)>,
     pub info: Info,
+    /// Any other top-level key.
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Ben>,
+    /// The `info` dictionary exactly as it appeared in the file.
+    #[serde(skip)]
+    raw_info: Vec<u8>,
 }
 
 impl Torrent {
+    /// Torrent with only the required fields set.
+    pub fn new(announce: Option<Url>, info: Info) -> Self {
+        Self {
+            announce,
+            announce_list: vec![],
+            comment: None,
+            created_by: None,
+            creation_date: None,
+            encoding: None,
+            url_list: vec![],
+            httpseeds: vec![],
+            nodes: vec![],
+            info,
+            extra: BTreeMap::new(),
+            raw_info: vec![],
+        }
+    }
+
+    /// Torrent out of a raw `info` dictionary, as exchanged with peers, and
+    /// the trackers it came with.
+    pub fn from_info(raw_info: &[u8], trackers: &[Url]) -> Result<Self> {
+        let info: Info = ben::from_bytes(raw_info)?;
+        let announce = trackers.first().cloned();
+        Ok(Self {
+            announce_list: tiers(trackers),
+            raw_info: raw_info.to_vec(),
+            ..Self::new(announce, info)
+        })
+    }
+
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
         let data = std::fs::read(path)?;
-        let t: Torrent = serde_bencode::from_bytes(&data)?;
+        Self::from_bytes(&data)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let options = Options {
+            spans: true,
+            ..Default::default()
+        };
+        let decoded = Ben::decode_with(data, options)?;
+        let mut t: Torrent = ben::from_decoded(&decoded)?;
+        let span = decoded
+            .spans
+            .get(&ben::Path::root().key("info"))
+            .context("Missing info dictionary")?;
+        t.raw_info = data[span].to_vec();
         Ok(t)
     }
+
+    /// SHA-1 of the raw `info` dictionary, so fields `Info` doesn't model are
+    /// still accounted for. Falls back to encoding `info` when there are no
+    /// raw bytes to hash.
+    pub fn info_hash(&self) -> Result<Hash> {
+        match self.raw_info.is_empty() {
+            true => Hash::encode(ben::to_bytes(&self.info)?),
+            false => Hash::encode(&self.raw_info),
+        }
+    }
+
+    /// Tracker URLs to try in order: `announce`, then `announce-list` tiers.
+    pub fn trackers(&self) -> Vec<Url> {
+        let mut urls: Vec<Url> = self.announce.iter().cloned().collect();
+        for url in self.announce_list.iter().flatten() {
+            if let Ok(url) = url.parse::<Url>() {
+                if !urls.contains(&url) {
+                    urls.push(url);
+                }
+            }
+        }
+        urls
+    }
+
+    /// Encodes the metainfo out of the modelled fields, keeping the raw
+    /// `info` dictionary when there is one so the info-hash doesn't change.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut ben = ben::to_value(self)?;
+        if let (Ben::Map(m), false) = (&mut ben, self.raw_info.is_empty()) {
+            m.insert(b"info".to_vec(), Ben::try_from(self.raw_info.as_slice())?);
+        }
+        Ok(ben.to_bytes())
+    }
+}
+
+/// One tier per tracker, or none when `announce` says it all.
+fn tiers(trackers: &[Url]) -> Vec<Vec<String>> {
+    match trackers.len() {
+        0 | 1 => vec![],
+        _ => trackers.iter().map(|u| vec![u.to_string()]).collect(),
+    }
 }
 
 mod url {
     use super::*;
 
-    pub fn deserialize<'de, D>(deserializer: D) -> std::result::Result<Url, D::Error>
+    pub fn deserialize<'de, D>(deserializer: D) -> std::result::Result<Option<Url>, D::Error>
     where
         D: Deserializer<'de>,
     {
         let s = String::deserialize(deserializer)?;
         let uri: Url = s.parse().map_err(serde::de::Error::custom)?;
-        Ok(uri)
+        Ok(Some(uri))
+    }
+
+    pub fn serialize<S>(url: &Option<Url>, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match url {
+            Some(url) => serializer.serialize_str(url.as_str()),
+            None => serializer.serialize_none(),
+        }
+    }
+}
+
+/// Optional fields that torrents in the wild often get wrong, kept as far as
+/// they make sense instead of failing the whole file.
+mod lenient {
+    use super::*;
+    use serde::de::DeserializeOwned;
+
+    /// Text as lossy UTF-8, since older torrents may use another `encoding`.
+    pub fn text<'de, D>(deserializer: D) -> std::result::Result<Option<String>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let ben = Ben::deserialize(deserializer)?;
+        Ok(ben
+            .as_bytes()
+            .map(|b| String::from_utf8_lossy(b).into_owned()))
+    }
+
+    /// The value if it fits `T`, `None` otherwise.
+    pub fn value<'de, D, T>(deserializer: D) -> std::result::Result<Option<T>, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: DeserializeOwned,
+    {
+        Ok(ben::from_value(&Ben::deserialize(deserializer)?).ok())
+    }
+
+    /// The entries that fit `T`, skipping the others.
+    pub fn list<'de, D, T>(deserializer: D) -> std::result::Result<Vec<T>, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: DeserializeOwned,
+    {
+        Ok(match Ben::deserialize(deserializer)? {
+            Ben::List(l) => l.iter().filter_map(|b| ben::from_value(b).ok()).collect(),
+            _ => vec![],
+        })
+    }
+}
+
+mod url_list {
+    use super::*;
+
+    /// A single URL or a list of them, skipping the ones that aren't text.
+    pub fn deserialize<'de, D>(deserializer: D) -> std::result::Result<Vec<String>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(match Ben::deserialize(deserializer)? {
+            Ben::List(l) => l.iter().filter_map(|b| ben::from_value(b).ok()).collect(),
+            one => ben::from_value(&one).into_iter().collect(),
+        })
+    }
+
+    pub fn serialize<S>(urls: &[String], serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        urls.serialize(serializer)
     }
 }
 
@@ -41,8 +256,93 @@ mod tests {
     fn test_open() {
         let t = Torrent::open("sample.torrent").unwrap();
         assert_eq!(
-            t.announce.to_string(),
+            t.announce.as_ref().unwrap().as_str(),
             "http://bittorrent-test-tracker.codecrafters.io/announce"
-        )
+        );
+        assert_eq!(
+            t.info_hash().unwrap().digest(),
+            "d69f91e6b2ae4c542468d1073a71d4ea13879a7f"
+        );
+    }
+
+    #[test]
+    fn test_trackerless() {
+        let json = serde_json::json!({
+            "length": 1, "name": "a", "piece length": 1, "pieces": "",
+        });
+        let raw = Ben::try_from(&json).unwrap().to_bytes();
+        let t = Torrent::from_info(&raw, &[]).unwrap();
+        assert_eq!(t.announce, None);
+        assert!(t.trackers().is_empty());
+        let again = Torrent::from_bytes(&t.to_bytes().unwrap()).unwrap();
+        assert_eq!(again.announce, None);
+        assert_eq!(again.info_hash().unwrap(), t.info_hash().unwrap());
+    }
+
+    #[test]
+    fn test_optional_fields() {
+        let json = serde_json::json!({
+            "announce": "http://a/announce",
+            "announce-list": [["http://a/announce"], ["udp://b:80"]],
+            "comment": "hi",
+            "creation date": 1700000000,
+            "url-list": "http://seed/",
+            "nodes": [["router.example", 6881]],
+            "x-custom": {"k": 1},
+            "info": {
+                "length": 1, "name": "a", "piece length": 1, "pieces": "",
+                "private": 1, "source": "x",
+            },
+        });
+        let t = Torrent::from_bytes(&Ben::try_from(&json).unwrap().to_bytes()).unwrap();
+        assert_eq!(t.announce_list.len(), 2);
+        assert_eq!(t.comment.as_deref(), Some("hi"));
+        assert_eq!(t.created_by, None);
+        assert_eq!(t.creation_date, Some(1700000000));
+        assert_eq!(t.url_list, vec!["http://seed/"]);
+        assert_eq!(t.nodes, vec![("router.example".to_string(), 6881)]);
+        assert_eq!(t.extra.keys().collect::<Vec<_>>(), vec!["x-custom"]);
+        assert!(t.info.is_private());
+        assert_eq!(t.info.source.as_deref(), Some("x"));
+    }
+
+    #[test]
+    fn test_lenient_optional_fields() {
+        let json = serde_json::json!({
+            "announce": "http://a/announce",
+            "comment": {"$hex": "c4e3"},
+            "created by": ["not", "text"],
+            "creation date": "yesterday",
+            "nodes": [["bad", 99999], ["good", 6881], "junk"],
+            "url-list": [{"$hex": "ff"}, "http://seed/"],
+            "info": {"length": 1, "name": "a", "piece length": 1, "pieces": ""},
+        });
+        let t = Torrent::from_bytes(&Ben::try_from(&json).unwrap().to_bytes()).unwrap();
+        assert_eq!(t.comment.as_deref(), Some("\u{fffd}\u{fffd}"));
+        assert_eq!(t.created_by, None);
+        assert_eq!(t.creation_date, None);
+        assert_eq!(t.nodes, vec![("good".to_string(), 6881)]);
+        assert_eq!(t.url_list, vec!["http://seed/"]);
+    }
+
+    #[test]
+    fn test_info_hash_keeps_unknown_fields() {
+        let json = serde_json::json!({
+            "announce": "http://a/announce",
+            "info": {
+                "length": 1, "name": "a", "piece length": 1, "pieces": "",
+                "md5sum": "0cc175b9c0f1b6a831c399e269772661",
+            },
+        });
+        let ben = Ben::try_from(&json).unwrap();
+        let t = Torrent::from_bytes(&ben.to_bytes()).unwrap();
+        let raw = ben.get("info").unwrap().to_bytes();
+        assert_eq!(t.info_hash().unwrap(), Hash::encode(raw).unwrap());
+        assert_ne!(
+            t.info_hash().unwrap(),
+            Hash::encode(ben::to_bytes(&t.info).unwrap()).unwrap()
+        );
+        let again = Torrent::from_bytes(&t.to_bytes().unwrap()).unwrap();
+        assert_eq!(again.info_hash().unwrap(), t.info_hash().unwrap());
     }
 }
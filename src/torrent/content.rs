@@ -0,0 +1,110 @@
+use crate::{
+    hash::{self, Hash},
+    torrent::{Info, Torrent},
+};
+use anyhow::{ensure, Context, Result};
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, Read},
+    path::{Component, Path, PathBuf},
+};
+
+impl Info {
+    /// Where each file of the content lives once stored under `root`, along
+    /// with its length. Single-file content is `root` itself.
+    pub fn locate(&self, root: &Path) -> Result<Vec<(PathBuf, u64)>> {
+        let multi = self.is_multi_file();
+        self.files()?
+            .into_iter()
+            .map(|f| {
+                if !multi {
+                    return Ok((root.to_path_buf(), f.length));
+                }
+                ensure!(
+                    f.path
+                        .components()
+                        .all(|c| matches!(c, Component::Normal(_))),
+                    "Unsafe file path in torrent: {}",
+                    f.path.display()
+                );
+                Ok((root.join(f.path), f.length))
+            })
+            .collect()
+    }
+}
+
+/// Reads a list of files as one continuous stream, opening them in turn.
+pub struct Content {
+    files: VecDeque<PathBuf>,
+    current: Option<File>,
+}
+
+impl Content {
+    pub fn new(files: impl IntoIterator<Item = PathBuf>) -> Self {
+        Self {
+            files: files.into_iter().collect(),
+            current: None,
+        }
+    }
+}
+
+impl Read for Content {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let file = match &mut self.current {
+                Some(file) => file,
+                None => match self.files.pop_front() {
+                    Some(path) => self.current.insert(File::open(&path).map_err(|e| {
+                        io::Error::new(e.kind(), format!("{}: {e}", path.display()))
+                    })?),
+                    None => return Ok(0),
+                },
+            };
+            match file.read(buf)? {
+                0 if !buf.is_empty() => self.current = None,
+                n => return Ok(n),
+            }
+        }
+    }
+}
+
+impl Torrent {
+    /// Rehashes the content stored under `root`, returning the indices of the
+    /// pieces that don't match.
+    pub fn verify(&self, root: &Path) -> Result<Vec<usize>> {
+        let files = self.info.locate(root)?;
+        let piece_length = usize::try_from(self.info.piece_length).context("Piece too large")?;
+        let hashes = hash::hash_pieces(Content::new(files.into_iter().map(|f| f.0)), piece_length)?;
+        let expected: Vec<Hash> = self.info.pieces().collect();
+        Ok((0..expected.len())
+            .filter(|&i| hashes.get(i) != Some(&expected[i]))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::torrent::Builder;
+
+    #[test]
+    fn test_verify() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("content");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("a"), vec![1; 40000]).unwrap();
+        std::fs::write(root.join("b"), vec![2; 10000]).unwrap();
+        let t = Builder::new(&root)
+            .tracker("http://a/announce".parse().unwrap())
+            .piece_length(16 * 1024)
+            .build()
+            .unwrap();
+        assert_eq!(t.verify(&root).unwrap(), Vec::<usize>::new());
+
+        std::fs::write(root.join("b"), vec![3; 5000]).unwrap();
+        assert_eq!(t.verify(&root).unwrap(), vec![2, 3]);
+
+        std::fs::remove_file(root.join("a")).unwrap();
+        assert!(t.verify(&root).is_err());
+    }
+}
@@ -0,0 +1,239 @@
+use crate::{
+    hash,
+    torrent::{
+        content::Content, info::File, tiers, Info, Torrent, MAX_PIECE_LENGTH, MIN_PIECE_LENGTH,
+    },
+};
+use anyhow::{ensure, Context, Result};
+use reqwest::Url;
+use std::{
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Piece count aimed for when choosing a piece length.
+const TARGET_PIECES: u64 = 1500;
+
+/// Builds the metainfo of a file, or of a directory and everything under it.
+pub struct Builder {
+    root: PathBuf,
+    trackers: Vec<Url>,
+    comment: Option<String>,
+    private: bool,
+    web_seeds: Vec<String>,
+    piece_length: Option<u64>,
+}
+
+impl Builder {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            trackers: vec![],
+            comment: None,
+            private: false,
+            web_seeds: vec![],
+            piece_length: None,
+        }
+    }
+
+    /// Adds a tracker. The first one becomes `announce`, and each one its own
+    /// tier in `announce-list` when there are several.
+    pub fn tracker(mut self, url: Url) -> Self {
+        self.trackers.push(url);
+        self
+    }
+
+    pub fn comment(mut self, comment: impl Into<String>) -> Self {
+        self.comment = Some(comment.into());
+        self
+    }
+
+    pub fn private(mut self, private: bool) -> Self {
+        self.private = private;
+        self
+    }
+
+    pub fn web_seed(mut self, url: impl Into<String>) -> Self {
+        self.web_seeds.push(url.into());
+        self
+    }
+
+    /// Overrides the piece length, otherwise chosen from the content size.
+    pub fn piece_length(mut self, length: u64) -> Self {
+        self.piece_length = Some(length);
+        self
+    }
+
+    pub fn build(self) -> Result<Torrent> {
+        let announce = self.trackers.first().context("No tracker given")?.clone();
+        let name = self
+            .root
+            .file_name()
+            .and_then(|n| n.to_str())
+            .context("Content name is not valid UTF-8")?
+            .to_string();
+
+        let single = self.root.is_file();
+        let files = match single {
+            true => vec![(self.root.clone(), vec![])],
+            false => walk(&self.root)?,
+        };
+        let mut entries = Vec::with_capacity(files.len());
+        let mut total = 0u64;
+        for (path, components) in &files {
+            let length = path.metadata()?.len();
+            total = total.checked_add(length).context("Content too large")?;
+            entries.push(File {
+                length,
+                path: components.clone(),
+            });
+        }
+        ensure!(!entries.is_empty(), "No files in {}", self.root.display());
+
+        let piece_length = self.piece_length.unwrap_or_else(|| choose(total));
+        ensure!(
+            piece_length.is_power_of_two(),
+            "Piece length {piece_length} is not a power of two"
+        );
+        ensure!(
+            (MIN_PIECE_LENGTH..=MAX_PIECE_LENGTH).contains(&piece_length),
+            "Piece length {piece_length} is outside the usual {MIN_PIECE_LENGTH}..={MAX_PIECE_LENGTH} range"
+        );
+        let size = usize::try_from(piece_length).context("Piece length too large")?;
+        let content = Content::new(files.into_iter().map(|f| f.0));
+        let pieces = hash::hash_pieces(content, size)?
+            .iter()
+            .flat_map(|h| h.as_bytes().to_vec())
+            .collect();
+
+        let info = Info {
+            length: single.then_some(total),
+            files: (!single).then_some(entries),
+            name,
+            piece_length,
+            pieces,
+            private: self.private.then_some(1),
+            source: None,
+        };
+        let creation_date = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        Ok(Torrent {
+            announce_list: tiers(&self.trackers),
+            comment: self.comment,
+            created_by: Some(
+                concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")).into(),
+            ),
+            creation_date: Some(creation_date as i64),
+            url_list: self.web_seeds,
+            ..Torrent::new(Some(announce), info)
+        })
+    }
+}
+
+/// Power of two giving about `TARGET_PIECES` pieces.
+fn choose(total: u64) -> u64 {
+    (total / TARGET_PIECES)
+        .next_power_of_two()
+        .clamp(MIN_PIECE_LENGTH, MAX_PIECE_LENGTH)
+}
+
+/// Files under `dir`, sorted, along with their path components relative to it.
+fn walk(dir: &Path) -> Result<Vec<(PathBuf, Vec<String>)>> {
+    let mut entries: Vec<_> = std::fs::read_dir(dir)?.collect::<std::io::Result<_>>()?;
+    entries.sort_by_key(|e| e.file_name());
+    let mut files = vec![];
+    for e in entries {
+        let name = e
+            .file_name()
+            .into_string()
+            .map_err(|n| anyhow::anyhow!("File name is not valid UTF-8: {n:?}"))?;
+        let path = e.path();
+        if path.is_dir() {
+            for (p, mut components) in walk(&path)? {
+                components.insert(0, name.clone());
+                files.push((p, components));
+            }
+        } else {
+            files.push((path, vec![name]));
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::Hash;
+
+    #[test]
+    fn test_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("content");
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+        std::fs::write(root.join("b.txt"), vec![b'b'; 20000]).unwrap();
+        std::fs::write(root.join("sub/a.txt"), vec![b'a'; 20000]).unwrap();
+
+        let t = Builder::new(&root)
+            .tracker("http://a/announce".parse().unwrap())
+            .tracker("udp://b:80".parse().unwrap())
+            .comment("release")
+            .private(true)
+            .web_seed("http://seed/")
+            .piece_length(16 * 1024)
+            .build()
+            .unwrap();
+        let t = Torrent::from_bytes(&t.to_bytes().unwrap()).unwrap();
+        assert_eq!(t.validate(), vec![]);
+        assert_eq!(t.info.name, "content");
+        assert_eq!(t.info.piece_count(), 3);
+        assert!(t.info.is_private());
+        assert_eq!(t.announce_list.len(), 2);
+        assert_eq!(t.url_list, vec!["http://seed/"]);
+        let paths: Vec<_> = t
+            .info
+            .files()
+            .unwrap()
+            .into_iter()
+            .map(|f| f.path)
+            .collect();
+        assert_eq!(
+            paths,
+            vec![PathBuf::from("b.txt"), PathBuf::from("sub/a.txt")]
+        );
+
+        let mut data = vec![b'b'; 20000];
+        data.extend(vec![b'a'; 20000]);
+        let expected: Vec<_> = data
+            .chunks(16 * 1024)
+            .map(|c| Hash::encode(c).unwrap())
+            .collect();
+        assert_eq!(t.info.pieces().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn test_single_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.bin");
+        std::fs::write(&path, vec![0; 100]).unwrap();
+        let t = Builder::new(&path)
+            .tracker("http://a/announce".parse().unwrap())
+            .build()
+            .unwrap();
+        assert!(!t.info.is_multi_file());
+        assert_eq!(t.info.length().unwrap(), 100);
+        assert_eq!(t.info.piece_length, MIN_PIECE_LENGTH);
+        assert_eq!(t.info.piece_count(), 1);
+        assert!(t.announce_list.is_empty());
+
+        for length in [0, 20000, 1024, MAX_PIECE_LENGTH * 2] {
+            let b = Builder::new(&path).tracker("http://a/announce".parse().unwrap());
+            assert!(b.piece_length(length).build().is_err(), "{length}");
+        }
+    }
+
+    #[test]
+    fn test_choose_piece_length() {
+        assert_eq!(choose(0), MIN_PIECE_LENGTH);
+        assert_eq!(choose(1500 << 20), 1 << 20);
+        assert_eq!(choose(u64::MAX), MAX_PIECE_LENGTH);
+    }
+}
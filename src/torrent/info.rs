@@ -1,21 +1,95 @@
 use crate::hash::Hash;
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use std::{cmp::min, path::PathBuf};
 
 #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
 pub struct Info {
-    pub length: u32,
+    /// Content length, in single-file mode.
+    pub(super) length: Option<u64>,
+    /// Content files, in multi-file mode.
+    pub(super) files: Option<Vec<File>>,
     pub name: String,
     #[serde(rename = "piece length")]
-    pub piece_length: u32,
+    pub piece_length: u64,
     #[serde(with = "serde_bytes")]
-    pieces: Vec<u8>,
+    pub(super) pieces: Vec<u8>,
+    /// Set to 1 to restrict peers to the ones given by trackers (BEP 27).
+    pub(super) private: Option<u8>,
+    pub source: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+pub struct File {
+    pub length: u64,
+    pub path: Vec<String>,
+}
+
+/// File of the content, located within the concatenated data of all pieces.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileSpan {
+    /// Path relative to the content root. The torrent name in single-file mode.
+    pub path: PathBuf,
+    pub length: u64,
+    pub offset: u64,
 }
 
 impl<'info> Info {
-    pub fn hash(&self) -> Result<Hash> {
-        let chunk = serde_bencode::to_bytes(self)?;
-        Hash::encode(chunk)
+    pub fn is_private(&self) -> bool {
+        self.private == Some(1)
+    }
+
+    pub fn is_multi_file(&self) -> bool {
+        self.files.is_some()
+    }
+
+    /// Total length of the content.
+    pub fn length(&self) -> Result<u64> {
+        match &self.files {
+            Some(files) => files
+                .iter()
+                .try_fold(0u64, |sum, f| sum.checked_add(f.length))
+                .context("Content length overflows"),
+            None => Ok(self.length.unwrap_or_default()),
+        }
+    }
+
+    /// Length of the piece at `index`, the last one being possibly shorter.
+    pub fn piece_length_at(&self, index: usize) -> Result<u64> {
+        let start = (index as u64)
+            .checked_mul(self.piece_length)
+            .context("Piece offset overflows")?;
+        let remains = self
+            .length()?
+            .checked_sub(start)
+            .filter(|r| *r > 0)
+            .with_context(|| format!("Piece index out of range: {index}"))?;
+        Ok(min(remains, self.piece_length))
+    }
+
+    pub fn files(&self) -> Result<Vec<FileSpan>> {
+        let Some(files) = &self.files else {
+            return Ok(vec![FileSpan {
+                path: PathBuf::from(&self.name),
+                length: self.length()?,
+                offset: 0,
+            }]);
+        };
+        let mut offset = 0u64;
+        files
+            .iter()
+            .map(|f| {
+                let span = FileSpan {
+                    path: f.path.iter().collect(),
+                    length: f.length,
+                    offset,
+                };
+                offset = offset
+                    .checked_add(f.length)
+                    .context("File offset overflows")?;
+                Ok(span)
+            })
+            .collect()
     }
 
     pub fn piece_at(&self, index: usize) -> Result<Hash> {
@@ -34,3 +108,72 @@ impl<'info> Info {
         self.pieces.len() / Hash::SIZE
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ben, ben::Ben};
+
+    fn info(json: serde_json::Value) -> Info {
+        ben::from_value(&Ben::try_from(&json).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_single_file() {
+        let i = info(serde_json::json!({
+            "length": 10, "name": "a.txt", "piece length": 16, "pieces": "",
+        }));
+        assert!(!i.is_multi_file());
+        assert_eq!(i.length().unwrap(), 10);
+        assert_eq!(i.files().unwrap()[0].path, PathBuf::from("a.txt"));
+    }
+
+    #[test]
+    fn test_multi_file() {
+        let i = info(serde_json::json!({
+            "files": [
+                {"length": 10, "path": ["dir", "a.txt"]},
+                {"length": 0, "path": ["empty"]},
+                {"length": 5, "path": ["b.txt"]},
+            ],
+            "name": "root", "piece length": 16, "pieces": "",
+        }));
+        assert!(i.is_multi_file());
+        assert_eq!(i.length().unwrap(), 15);
+        let offsets: Vec<_> = i
+            .files()
+            .unwrap()
+            .iter()
+            .map(|f| (f.path.clone(), f.offset))
+            .collect();
+        assert_eq!(
+            offsets,
+            vec![
+                (PathBuf::from("dir/a.txt"), 0),
+                (PathBuf::from("empty"), 10),
+                (PathBuf::from("b.txt"), 10),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_large_content() {
+        let i = info(serde_json::json!({
+            "length": 5u64 << 30, "name": "big", "piece length": 1u64 << 30, "pieces": "",
+        }));
+        assert_eq!(i.length().unwrap(), 5 << 30);
+        assert_eq!(i.piece_length_at(4).unwrap(), 1 << 30);
+        assert!(i.piece_length_at(5).is_err());
+
+        let i = info(serde_json::json!({
+            "files": [
+                {"length": i64::MAX, "path": ["a"]},
+                {"length": i64::MAX, "path": ["b"]},
+                {"length": i64::MAX, "path": ["c"]},
+            ],
+            "name": "x", "piece length": 16, "pieces": "",
+        }));
+        assert!(i.length().is_err());
+        assert!(i.files().is_err());
+    }
+}
@@ -0,0 +1,218 @@
+use crate::{
+    ben::Path,
+    hash::Hash,
+    torrent::{Torrent, MAX_PIECE_LENGTH, MIN_PIECE_LENGTH},
+};
+use reqwest::Url;
+use std::fmt::{Display, Formatter};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Level {
+    /// Unusual, but the torrent can still be used.
+    Warning,
+    /// Breaks an invariant the client relies on.
+    Error,
+}
+
+/// Problem found in a metainfo file, located by the path of the offending node.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub level: Level,
+    pub path: Path,
+    pub message: String,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let level = match self.level {
+            Level::Warning => "warning",
+            Level::Error => "error",
+        };
+        write!(f, "{level}: {}: {}", self.path, self.message)
+    }
+}
+
+#[derive(Default)]
+struct Report(Vec<Diagnostic>);
+
+impl Report {
+    fn push(&mut self, level: Level, path: Path, message: String) {
+        self.0.push(Diagnostic {
+            level,
+            path,
+            message,
+        });
+    }
+
+    fn error(&mut self, path: Path, message: String) {
+        self.push(Level::Error, path, message)
+    }
+
+    fn warning(&mut self, path: Path, message: String) {
+        self.push(Level::Warning, path, message)
+    }
+}
+
+impl Torrent {
+    /// Checks the invariants the rest of the client assumes about a torrent.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut r = Report::default();
+        self.validate_trackers(&mut r);
+        self.validate_pieces(&mut r);
+        self.validate_files(&mut r);
+        r.0
+    }
+
+    fn validate_trackers(&self, r: &mut Report) {
+        let path = Path::root().key("announce");
+        if let Some(url) = &self.announce {
+            if !matches!(url.scheme(), "http" | "https" | "udp") {
+                let msg = format!("unsupported tracker scheme {:?}", url.scheme());
+                r.error(path, msg);
+            }
+        }
+        for (i, tier) in self.announce_list.iter().enumerate() {
+            for (j, url) in tier.iter().enumerate() {
+                let path = Path::root().key("announce-list").index(i).index(j);
+                match url.parse::<Url>() {
+                    Ok(u) if matches!(u.scheme(), "http" | "https" | "udp") => {}
+                    Ok(u) => {
+                        r.warning(path, format!("unsupported tracker scheme {:?}", u.scheme()))
+                    }
+                    Err(e) => r.warning(path, format!("invalid URL {url:?}: {e}")),
+                }
+            }
+        }
+        for (i, url) in self.url_list.iter().enumerate() {
+            let path = Path::root().key("url-list").index(i);
+            match url.parse::<Url>() {
+                Ok(u) if matches!(u.scheme(), "http" | "https") => {}
+                Ok(u) => r.warning(
+                    path,
+                    format!("unsupported web seed scheme {:?}", u.scheme()),
+                ),
+                Err(e) => r.warning(path, format!("invalid URL {url:?}: {e}")),
+            }
+        }
+    }
+
+    fn validate_pieces(&self, r: &mut Report) {
+        let info = &self.info;
+        let path = Path::root().key("info");
+
+        let pieces = info.pieces.len();
+        let partial = pieces % Hash::SIZE;
+        if partial > 0 {
+            let msg = format!("length {pieces} ends with a partial hash of {partial} bytes");
+            r.error(path.clone().key("pieces"), msg);
+        }
+
+        let piece_length = info.piece_length;
+        let at = path.clone().key("piece length");
+        if piece_length == 0 {
+            r.error(at, "must be greater than zero".into());
+            return;
+        }
+        if !piece_length.is_power_of_two() {
+            r.warning(at.clone(), format!("{piece_length} is not a power of two"));
+        }
+        if !(MIN_PIECE_LENGTH..=MAX_PIECE_LENGTH).contains(&piece_length) {
+            let msg = format!(
+                "{piece_length} is outside the usual {MIN_PIECE_LENGTH}..={MAX_PIECE_LENGTH} range"
+            );
+            r.warning(at, msg);
+        }
+
+        let Ok(length) = info.length() else {
+            r.error(path, "content length overflows".into());
+            return;
+        };
+        let expected = length.div_ceil(piece_length);
+        let count = info.piece_count() as u64;
+        if count != expected {
+            let msg = format!(
+                "{count} pieces, but {length} bytes in pieces of {piece_length} need {expected}"
+            );
+            r.error(path.key("pieces"), msg);
+        }
+    }
+
+    fn validate_files(&self, r: &mut Report) {
+        let info = &self.info;
+        let path = Path::root().key("info");
+        if let Some(msg) = unsafe_component(&info.name) {
+            r.error(path.clone().key("name"), msg);
+        }
+        match (&info.length, &info.files) {
+            (Some(_), Some(_)) => r.error(path.clone(), "both length and files are set".into()),
+            (None, None) => r.error(path.clone(), "neither length nor files is set".into()),
+            _ => {}
+        }
+        for (i, f) in info.files.iter().flatten().enumerate() {
+            let path = path.clone().key("files").index(i).key("path");
+            if f.path.is_empty() {
+                r.error(path.clone(), "empty path".into());
+            }
+            for (j, c) in f.path.iter().enumerate() {
+                if let Some(msg) = unsafe_component(c) {
+                    r.error(path.clone().index(j), msg);
+                }
+            }
+        }
+    }
+}
+
+/// Why a path component can't be safely joined to the download directory.
+fn unsafe_component(c: &str) -> Option<String> {
+    match c {
+        "" => Some("empty path component".into()),
+        "." | ".." => Some(format!("relative path component {c:?}")),
+        c if c.contains(['/', '\\']) => Some(format!("path separator in component {c:?}")),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ben::Ben;
+
+    fn torrent(json: serde_json::Value) -> Torrent {
+        Torrent::from_bytes(&Ben::try_from(&json).unwrap().to_bytes()).unwrap()
+    }
+
+    fn messages(t: &Torrent) -> Vec<String> {
+        t.validate().iter().map(|d| d.to_string()).collect()
+    }
+
+    #[test]
+    fn test_sample_is_valid() {
+        let t = Torrent::open("sample.torrent").unwrap();
+        assert_eq!(t.validate(), vec![]);
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let t = torrent(serde_json::json!({
+            "announce": "ftp://a/announce",
+            "info": {
+                "files": [
+                    {"length": 20000, "path": ["..", "a"]},
+                    {"length": 20000, "path": []},
+                ],
+                "name": "x", "piece length": 20000, "pieces": "x".repeat(21),
+            },
+        }));
+        assert_eq!(
+            messages(&t),
+            vec![
+                r#"error: announce: unsupported tracker scheme "ftp""#,
+                "error: info.pieces: length 21 ends with a partial hash of 1 bytes",
+                "warning: info.piece length: 20000 is not a power of two",
+                "error: info.pieces: 1 pieces, but 40000 bytes in pieces of 20000 need 2",
+                r#"error: info.files[0].path[0]: relative path component "..""#,
+                "error: info.files[1].path: empty path",
+            ]
+        );
+    }
+}